@@ -0,0 +1,6 @@
+CREATE TABLE user_preferences (
+    user_id BIGINT PRIMARY KEY,
+    language_code TEXT NOT NULL,
+    voice_name TEXT NOT NULL,
+    ssml_gender TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+CREATE TABLE channel_links (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL
+);
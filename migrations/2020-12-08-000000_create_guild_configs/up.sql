@@ -0,0 +1,4 @@
+CREATE TABLE guild_configs (
+    guild_id BIGINT PRIMARY KEY,
+    tts_provider TEXT
+);
@@ -0,0 +1 @@
+ALTER TABLE user_preferences ADD COLUMN provider TEXT NOT NULL DEFAULT 'google';
@@ -0,0 +1 @@
+ALTER TABLE user_preferences ADD COLUMN ssml BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+ALTER TABLE user_preferences
+    DROP COLUMN speaking_rate,
+    DROP COLUMN pitch,
+    DROP COLUMN volume_gain_db;
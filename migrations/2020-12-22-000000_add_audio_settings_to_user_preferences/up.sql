@@ -0,0 +1,4 @@
+ALTER TABLE user_preferences
+    ADD COLUMN speaking_rate DOUBLE PRECISION NOT NULL DEFAULT 1.0,
+    ADD COLUMN pitch DOUBLE PRECISION NOT NULL DEFAULT 0.0,
+    ADD COLUMN volume_gain_db DOUBLE PRECISION NOT NULL DEFAULT 0.0;
@@ -0,0 +1,6 @@
+CREATE TABLE pronunciations (
+    guild_id BIGINT NOT NULL,
+    word TEXT NOT NULL,
+    replacement TEXT NOT NULL,
+    PRIMARY KEY (guild_id, word)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    DROP COLUMN announce_window_secs,
+    DROP COLUMN announce_speaker;
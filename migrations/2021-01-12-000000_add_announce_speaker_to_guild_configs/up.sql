@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    ADD COLUMN announce_speaker BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN announce_window_secs INTEGER NOT NULL DEFAULT 60;
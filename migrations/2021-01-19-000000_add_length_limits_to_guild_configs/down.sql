@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    DROP COLUMN overflow_policy,
+    DROP COLUMN max_message_length;
@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    ADD COLUMN max_message_length INTEGER NOT NULL DEFAULT 500,
+    ADD COLUMN overflow_policy TEXT NOT NULL DEFAULT 'truncate';
@@ -0,0 +1,5 @@
+ALTER TABLE guild_configs
+    DROP COLUMN rate_limit_notify,
+    DROP COLUMN rate_limit_action,
+    DROP COLUMN guild_rate_limit,
+    DROP COLUMN user_rate_limit;
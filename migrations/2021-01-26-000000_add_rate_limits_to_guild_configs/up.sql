@@ -0,0 +1,5 @@
+ALTER TABLE guild_configs
+    ADD COLUMN user_rate_limit INTEGER NOT NULL DEFAULT 6,
+    ADD COLUMN guild_rate_limit INTEGER NOT NULL DEFAULT 20,
+    ADD COLUMN rate_limit_action TEXT NOT NULL DEFAULT 'drop',
+    ADD COLUMN rate_limit_notify BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    DROP COLUMN idle_timeout_mins,
+    DROP COLUMN auto_join;
@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    ADD COLUMN auto_join BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN idle_timeout_mins INTEGER NOT NULL DEFAULT 10;
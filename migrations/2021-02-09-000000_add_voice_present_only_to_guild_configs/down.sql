@@ -0,0 +1,2 @@
+ALTER TABLE guild_configs
+    DROP COLUMN voice_present_only;
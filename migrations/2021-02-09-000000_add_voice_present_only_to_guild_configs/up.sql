@@ -0,0 +1,2 @@
+ALTER TABLE guild_configs
+    ADD COLUMN voice_present_only BOOLEAN NOT NULL DEFAULT FALSE;
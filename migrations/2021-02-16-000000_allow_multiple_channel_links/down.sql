@@ -0,0 +1,7 @@
+DELETE FROM channel_links a
+    USING channel_links b
+    WHERE a.guild_id = b.guild_id AND a.channel_id > b.channel_id;
+ALTER TABLE channel_links
+    DROP COLUMN voice_channel_id,
+    DROP CONSTRAINT channel_links_pkey,
+    ADD PRIMARY KEY (guild_id);
@@ -0,0 +1,4 @@
+ALTER TABLE channel_links
+    DROP CONSTRAINT channel_links_pkey,
+    ADD PRIMARY KEY (guild_id, channel_id),
+    ADD COLUMN voice_channel_id BIGINT;
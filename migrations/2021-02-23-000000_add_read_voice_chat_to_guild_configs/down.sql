@@ -0,0 +1,2 @@
+ALTER TABLE guild_configs
+    DROP COLUMN read_voice_chat;
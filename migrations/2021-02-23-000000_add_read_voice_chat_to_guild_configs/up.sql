@@ -0,0 +1,2 @@
+ALTER TABLE guild_configs
+    ADD COLUMN read_voice_chat BOOLEAN NOT NULL DEFAULT FALSE;
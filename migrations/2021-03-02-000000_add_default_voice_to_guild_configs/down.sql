@@ -0,0 +1,6 @@
+ALTER TABLE guild_configs
+    DROP COLUMN read_everyone,
+    DROP COLUMN default_voice_gender,
+    DROP COLUMN default_voice_language,
+    DROP COLUMN default_voice_name,
+    DROP COLUMN default_voice_provider;
@@ -0,0 +1,6 @@
+ALTER TABLE guild_configs
+    ADD COLUMN default_voice_provider TEXT,
+    ADD COLUMN default_voice_name TEXT,
+    ADD COLUMN default_voice_language TEXT,
+    ADD COLUMN default_voice_gender TEXT,
+    ADD COLUMN read_everyone BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+DROP TABLE command_permissions;
+
+ALTER TABLE guild_configs
+    DROP COLUMN dj_role_id;
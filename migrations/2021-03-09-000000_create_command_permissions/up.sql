@@ -0,0 +1,9 @@
+ALTER TABLE guild_configs
+    ADD COLUMN dj_role_id BIGINT;
+
+CREATE TABLE command_permissions (
+    guild_id BIGINT NOT NULL,
+    command TEXT NOT NULL,
+    level TEXT NOT NULL,
+    PRIMARY KEY (guild_id, command)
+);
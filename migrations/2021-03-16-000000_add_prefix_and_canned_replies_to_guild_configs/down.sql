@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    DROP COLUMN canned_replies,
+    DROP COLUMN prefix;
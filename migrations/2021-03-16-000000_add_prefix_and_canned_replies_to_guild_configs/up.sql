@@ -0,0 +1,3 @@
+ALTER TABLE guild_configs
+    ADD COLUMN prefix TEXT,
+    ADD COLUMN canned_replies BOOLEAN NOT NULL DEFAULT TRUE;
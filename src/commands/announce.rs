@@ -0,0 +1,70 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::models::guild_config::GuildConfig;
+use crate::preferences::{command_prefix, guild_config, update_guild_config};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+/// Toggles saying "<nickname> says" before messages. The name is skipped
+/// when the same person keeps talking within the window, e.g.
+/// `g/announce on 120`.
+#[command]
+async fn announce(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let enable = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        Ok(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Use {0}announce on [window in seconds] or {0}announce off", prefix)).await);
+            return Ok(());
+        },
+        Err(_) => {
+            let config = guild_config(ctx, guild_id).await;
+            let response = if config.announce_speaker {
+                format!("I announce who's speaking, unless they spoke in the last {} seconds", config.announce_window_secs)
+            } else {
+                format!("I don't announce who's speaking, turn it on with {}announce on [window in seconds]", prefix)
+            };
+            check_msg(msg.channel_id.say(&ctx.http, response).await);
+            return Ok(());
+        },
+    };
+    let (min, max) = GuildConfig::ANNOUNCE_WINDOW_RANGE;
+    let window = match args.single::<i32>() {
+        Ok(window) if window >= min && window <= max => Some(window),
+        Ok(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("The window needs to be between {} and {} seconds", min, max)).await);
+            return Ok(());
+        },
+        Err(_) => None,
+    };
+
+    let config = match update_guild_config(ctx, guild_id, |config| {
+        config.announce_speaker = enable;
+        if let Some(window) = window {
+            config.announce_window_secs = window;
+        }
+    }).await {
+        Ok(config) => config,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        },
+    };
+    let response = if config.announce_speaker {
+        format!("I'll say who's speaking, unless they spoke in the last {} seconds", config.announce_window_secs)
+    } else {
+        "I'll stop saying who's speaking".to_string()
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use crate::check_msg;
+use crate::tts::cache::AudioCache;
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    macros::command,
+};
+
+#[command]
+async fn cache(ctx: &Context, msg: &Message) -> CommandResult {
+    let audio_cache = ctx.data.read().await
+        .get::<AudioCache>().cloned().expect("Expected AudioCache in TypeMap.");
+    let stats = audio_cache.stats();
+    let lookups = stats.hits + stats.misses;
+    let hit_rate = if lookups > 0 {
+        stats.hits as f64 * 100.0 / lookups as f64
+    } else {
+        0.0
+    };
+
+    let response = format!(
+        "Cached {} clips, {:.1} of {:.1} MB\n> hits: {}\n> misses: {}\n> hit rate: {:.1}%",
+        stats.entries,
+        stats.bytes as f64 / 1_048_576.0,
+        stats.max_bytes as f64 / 1_048_576.0,
+        stats.hits,
+        stats.misses,
+        hit_rate,
+    );
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::models::guild_config::GuildConfig;
+use crate::permissions::{PermissionLevel, find_role, member_level};
+use crate::preferences::{guild_config, guild_provider, update_guild_config};
+use crate::tts::{
+    catalog::{VoiceCatalog, VoiceLookup},
+    provider::TtsProviders,
+};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+/// Settings only Manage Server may change, like g/djrole.
+const ADMIN_KEYS: [&str; 1] = ["dj_role"];
+
+/// Lists every setting and its value.
+#[command]
+#[sub_commands(config_get, config_set, config_reset)]
+async fn config(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let config = guild_config(ctx, guild_id).await;
+    let mut response = "Settings:\n".to_string();
+    for key in GuildConfig::KEYS.iter() {
+        response.push_str(&format!("> {}: {}\n", key, config.get(key).unwrap_or_default()));
+    }
+    response.push_str(&format!("Change them with {0}config set {{key}} {{value}} or {0}config reset {{key}}", config.prefix()));
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+#[command("get")]
+async fn config_get(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let key = args.single::<String>().map(|key| key.to_lowercase()).unwrap_or_default();
+    let config = guild_config(ctx, guild_id).await;
+    let response = match config.get(&key) {
+        Some(value) => format!("{} is {}", key, value),
+        None => unknown_key(&key),
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+#[command("set")]
+async fn config_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let key = args.single::<String>().map(|key| key.to_lowercase()).unwrap_or_default();
+    let value = args.rest().trim().to_string();
+    if !GuildConfig::KEYS.contains(&key.as_str()) {
+        check_msg(msg.channel_id.say(&ctx.http, unknown_key(&key)).await);
+        return Ok(());
+    }
+    if value.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("What should {} be? Use config set {{key}} {{value}}", key)).await);
+        return Ok(());
+    }
+    if !may_change(ctx, msg, guild_id, &key).await {
+        return Ok(());
+    }
+
+    let change = match key.as_str() {
+        "provider" => provider_change(ctx, &value).await,
+        "default_voice" => default_voice_change(ctx, guild_id, &value).await,
+        "dj_role" => dj_role_change(ctx, guild_id, &value).await,
+        _ => {
+            // Validate on a copy, so bad values are reported before anything is saved
+            let mut validated = guild_config(ctx, guild_id).await;
+            validated.set(&key, &value).map(|()| {
+                let (key, value) = (key.to_string(), value.to_string());
+                Box::new(move |config: &mut GuildConfig| {
+                    let _ = config.set(&key, &value);
+                }) as ConfigChange
+            })
+        },
+    };
+    let change = match change {
+        Ok(change) => change,
+        Err(why) => {
+            check_msg(msg.channel_id.say(&ctx.http, why).await);
+            return Ok(());
+        },
+    };
+    let config = match update_guild_config(ctx, guild_id, change).await {
+        Ok(config) => config,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        },
+    };
+    check_msg(msg.channel_id.say(&ctx.http, &format!("{} is now {}", key, config.get(&key).unwrap_or_default())).await);
+    Ok(())
+}
+
+#[command("reset")]
+async fn config_reset(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let key = args.single::<String>().map(|key| key.to_lowercase()).unwrap_or_default();
+    if !GuildConfig::KEYS.contains(&key.as_str()) {
+        check_msg(msg.channel_id.say(&ctx.http, unknown_key(&key)).await);
+        return Ok(());
+    }
+    if !may_change(ctx, msg, guild_id, &key).await {
+        return Ok(());
+    }
+
+    let config = match update_guild_config(ctx, guild_id, |config| {
+        if let Err(why) = config.reset(&key) {
+            println!("Err resetting {}: {}", key, why);
+        }
+    }).await {
+        Ok(config) => config,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        },
+    };
+    check_msg(msg.channel_id.say(&ctx.http, &format!("{} is back to {}", key, config.get(&key).unwrap_or_default())).await);
+    Ok(())
+}
+
+type ConfigChange = Box<dyn FnOnce(&mut GuildConfig) + Send>;
+
+fn unknown_key(key: &str) -> String {
+    format!("I don't have a {} setting, pick one of: {}", key, GuildConfig::KEYS.join(", "))
+}
+
+/// Some settings hand out permissions, those stay with Manage Server even
+/// when the DJ role may change the rest.
+async fn may_change(ctx: &Context, msg: &Message, guild_id: GuildId, key: &str) -> bool {
+    if !ADMIN_KEYS.contains(&key) || member_level(ctx, guild_id, msg).await >= PermissionLevel::Admin {
+        return true;
+    }
+    check_msg(msg.channel_id.say(&ctx.http, &format!("Only people with Manage Server can change {}", key)).await);
+    false
+}
+
+async fn provider_change(ctx: &Context, value: &str) -> Result<ConfigChange, String> {
+    let providers = ctx.data.read().await
+        .get::<TtsProviders>().cloned().expect("Expected TtsProviders in TypeMap.");
+    let provider_id = value.to_lowercase();
+    if providers.get(&provider_id).is_none() {
+        return Err(format!("I don't know that one, pick one of: {}", providers.ids().join(", ")));
+    }
+    Ok(Box::new(move |config| config.tts_provider = Some(provider_id)))
+}
+
+async fn default_voice_change(ctx: &Context, guild_id: GuildId, value: &str) -> Result<ConfigChange, String> {
+    let provider = guild_provider(ctx, Some(guild_id)).await;
+    let catalog = ctx.data.read().await
+        .get::<VoiceCatalog>().cloned().expect("Expected VoiceCatalog in TypeMap.");
+    match catalog.lookup(provider.id(), value).await {
+        Ok(VoiceLookup::Found(voice)) => {
+            let voice = voice.to_voice();
+            Ok(Box::new(move |config| config.set_default_voice(Some(voice))))
+        },
+        Ok(VoiceLookup::DidYouMean(suggestion)) => Err(format!("I don't know that voice, did you mean {}?", suggestion)),
+        Ok(VoiceLookup::Unknown) => Err("I don't know that voice :7".to_string()),
+        Err(why) => {
+            println!("Err listing voices: {}", why);
+            Err(why.user_message(guild_config(ctx, guild_id).await.prefix()))
+        },
+    }
+}
+
+async fn dj_role_change(ctx: &Context, guild_id: GuildId, value: &str) -> Result<ConfigChange, String> {
+    match find_role(ctx, guild_id, value).await {
+        Some(role) => Ok(Box::new(move |config| config.dj_role_id = Some(role.id.0 as i64))),
+        None => Err("I can't find that role on this server".to_string()),
+    }
+}
@@ -0,0 +1,112 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::preferences::{command_prefix, guild_config, guild_provider, update_guild_config};
+use crate::tts::catalog::{VoiceCatalog, VoiceLookup};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+/// Sets the voice unregistered users are read with once g/readeveryone is
+/// on, e.g. `g/defaultvoice en-GB-Wavenet-A` or `g/defaultvoice clear`.
+#[command]
+async fn defaultvoice(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let voice_name = match args.single::<String>() {
+        Ok(voice_name) => voice_name,
+        Err(_) => {
+            let response = match guild_config(ctx, guild_id).await.default_voice() {
+                Some(voice) => format!("Unregistered users get {0}, change it with {1}defaultvoice {{voice}} or {1}defaultvoice clear", voice.name, prefix),
+                None => format!("There's no default voice, pick one with {}defaultvoice {{voice}}", prefix),
+            };
+            check_msg(msg.channel_id.say(&ctx.http, response).await);
+            return Ok(());
+        },
+    };
+    if voice_name.eq_ignore_ascii_case("clear") {
+        if update_guild_config(ctx, guild_id, |config| config.set_default_voice(None)).await.is_err() {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        }
+        check_msg(msg.channel_id.say(&ctx.http, "Default voice cleared, only registered users are read").await);
+        return Ok(());
+    }
+
+    let provider = guild_provider(ctx, Some(guild_id)).await;
+    let catalog = ctx.data.read().await
+        .get::<VoiceCatalog>().cloned().expect("Expected VoiceCatalog in TypeMap.");
+    let lookup = match catalog.lookup(provider.id(), &voice_name).await {
+        Ok(lookup) => lookup,
+        Err(why) => {
+            println!("Err listing voices: {}", why);
+            check_msg(msg.channel_id.say(&ctx.http, why.user_message(&prefix)).await);
+            return Ok(());
+        },
+    };
+    match lookup {
+        VoiceLookup::Found(voice) => {
+            let config = match update_guild_config(ctx, guild_id, |config| config.set_default_voice(Some(voice.to_voice()))).await {
+                Ok(config) => config,
+                Err(_) => {
+                    check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+                    return Ok(());
+                },
+            };
+            let response = if config.read_everyone {
+                format!("Unregistered users now get {}", voice.name)
+            } else {
+                format!("Default voice set to {}, turn on {}readeveryone to use it", voice.name, prefix)
+            };
+            check_msg(msg.channel_id.say(&ctx.http, response).await);
+        },
+        VoiceLookup::DidYouMean(suggestion) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("I don't know that voice, did you mean {}?", suggestion)).await);
+        },
+        VoiceLookup::Unknown => {
+            check_msg(msg.channel_id.say(&ctx.http, "I don't know that voice :7").await);
+        },
+    }
+    Ok(())
+}
+
+/// Toggles reading messages from people who never registered a voice, using
+/// the guild's default voice.
+#[command]
+async fn readeveryone(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let enable = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            let state = if guild_config(ctx, guild_id).await.read_everyone { "on" } else { "off" };
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Reading everyone is {0}, use {1}readeveryone on or {1}readeveryone off", state, prefix)).await);
+            return Ok(());
+        },
+    };
+
+    let config = match update_guild_config(ctx, guild_id, |config| config.read_everyone = enable).await {
+        Ok(config) => config,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        },
+    };
+    let response = match config.default_voice() {
+        _ if !enable => "I'll only read people who registered a voice".to_string(),
+        Some(voice) => format!("I'll read everyone, people without a voice get {}", voice.name),
+        None => format!("I'll read everyone once there's a default voice, pick one with {}defaultvoice {{voice}}", prefix),
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
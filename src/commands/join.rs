@@ -1,5 +1,5 @@
 use crate::check_msg;
-use crate::VoiceManager;
+use crate::presence;
 use serenity::prelude::*;
 use serenity::model::prelude::*;
 use serenity::framework::standard::{
@@ -32,10 +32,7 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
         }
     };
 
-    let manager_lock = ctx.data.read().await.get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
-    let mut manager = manager_lock.lock().await;
-
-    if manager.join(guild_id, connect_to).is_some() {
+    if presence::join(ctx, guild_id, connect_to).await {
         check_msg(msg.channel_id.say(&ctx.http, &format!("Joined {}", connect_to.mention())).await);
     } else {
         check_msg(msg.channel_id.say(&ctx.http, "Error joining the channel").await);
@@ -0,0 +1,77 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::models::guild_config::GuildConfig;
+use crate::normalize::OverflowPolicy;
+use crate::preferences::{command_prefix, guild_config, update_guild_config};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+fn describe_limit(config: &GuildConfig) -> String {
+    let overflow = match config.overflow_policy() {
+        OverflowPolicy::Truncate => "cut off with \"and more\"",
+        OverflowPolicy::Skip => "skipped",
+        OverflowPolicy::Split => "read in parts",
+    };
+    format!("Messages over {} characters are {}", config.max_message_length, overflow)
+}
+
+/// Sets how long a message may be and what happens to longer ones, e.g.
+/// `g/length 300 split`. Either part can be left out.
+#[command]
+async fn length(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let (min, max) = GuildConfig::MESSAGE_LENGTH_RANGE;
+    let policies = OverflowPolicy::ALL.iter()
+        .map(|policy| policy.as_str())
+        .collect::<Vec<&str>>()
+        .join("|");
+    let usage = format!("Use {0}length [{1}-{2}] [{3}], e.g. {0}length 300 split", prefix, min, max, policies);
+
+    let mut max_length = None;
+    let mut policy = None;
+    for arg in args.raw() {
+        if let Ok(value) = arg.parse::<i32>() {
+            if value < min || value > max {
+                check_msg(msg.channel_id.say(&ctx.http, &format!("The limit needs to be between {} and {} characters", min, max)).await);
+                return Ok(());
+            }
+            max_length = Some(value);
+        } else if let Ok(value) = arg.parse::<OverflowPolicy>() {
+            policy = Some(value);
+        } else {
+            check_msg(msg.channel_id.say(&ctx.http, usage).await);
+            return Ok(());
+        }
+    }
+
+    if max_length.is_none() && policy.is_none() {
+        let config = guild_config(ctx, guild_id).await;
+        check_msg(msg.channel_id.say(&ctx.http, &format!("{}\n{}", describe_limit(&config), usage)).await);
+        return Ok(());
+    }
+    let config = match update_guild_config(ctx, guild_id, |config| {
+        if let Some(max_length) = max_length {
+            config.max_message_length = max_length;
+        }
+        if let Some(policy) = policy {
+            config.overflow_policy = policy.as_str().to_string();
+        }
+    }).await {
+        Ok(config) => config,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        },
+    };
+    check_msg(msg.channel_id.say(&ctx.http, describe_limit(&config)).await);
+    Ok(())
+}
@@ -1,44 +1,186 @@
 use crate::check_msg;
-use crate::ChannelRegistry;
+use crate::db::{self, Database, SAVE_FAILED};
+use crate::preferences::{
+    ChannelLink,
+    ChannelRegistry,
+    guild_config,
+    update_guild_config,
+    save_channel_link,
+    delete_channel_link,
+    command_prefix,
+};
 use serenity::prelude::*;
 use serenity::model::prelude::*;
 use serenity::framework::standard::{
     CommandResult,
+    Args,
     macros::command,
 };
 
+/// Reads this channel's messages out, e.g. `g/link` or `g/link #general`
+/// to only read them into that voice channel.
 #[command]
-async fn link(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild_id;
-    match msg.guild_id {
-        Some(v) => guild_id = v,
+async fn link(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
         None => return Ok(())
     };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let voice_channel_id = match args.single::<ChannelId>() {
+        Ok(channel_id) => {
+            let is_voice = ctx.cache.guild_channel_field(channel_id, |channel| {
+                channel.guild_id == guild_id && channel.kind == ChannelType::Voice
+            }).await;
+            if is_voice != Some(true) {
+                check_msg(msg.channel_id.say(&ctx.http, "That isn't a voice channel on this server").await);
+                return Ok(());
+            }
+            Some(channel_id)
+        },
+        Err(_) if args.is_empty() => None,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Use {0}link or {0}link {{voice channel}} to only read into that one", prefix)).await);
+            return Ok(());
+        },
+    };
+    let link = ChannelLink {
+        channel_id: msg.channel_id.0,
+        voice_channel_id: voice_channel_id.map(|channel_id| channel_id.0),
+    };
 
-    {
+    let (channel_map_lock, pool) = {
         let data_read = ctx.data.read().await;
-        let channel_map_lock = data_read.get::<ChannelRegistry>().expect("Unable to read channel map").clone();
+        (
+            data_read.get::<ChannelRegistry>().cloned().expect("Expected ChannelRegistry in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    if let Err(why) = db::run(&pool, move |pool| save_channel_link(pool, guild_id.0, &link)).await {
+        println!("Error saving channel link: {:?}", why);
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    {
         let mut channel_map = channel_map_lock.write().await;
-        channel_map.insert(guild_id.0, msg.channel_id.0);
+        let links = channel_map.entry(guild_id.0).or_default();
+        links.retain(|existing| existing.channel_id != link.channel_id);
+        links.push(link);
     }
-    check_msg(msg.channel_id.say(&ctx.http, "Now using this channel for TTS input :3").await);
+    let response = match voice_channel_id {
+        Some(channel_id) => format!("Now reading this channel into {} :3", channel_id.mention()),
+        None => "Now using this channel for TTS input :3".to_string(),
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
     Ok(())
 }
 
+/// Stops reading a channel, this one unless another is given.
 #[command]
-async fn unlink(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild_id;
-    match msg.guild_id {
-        Some(v) => guild_id = v,
+async fn unlink(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
         None => return Ok(())
     };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let channel_id = match args.single::<ChannelId>() {
+        Ok(channel_id) => channel_id,
+        Err(_) if args.is_empty() => msg.channel_id,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Use {0}unlink or {0}unlink {{channel}}", prefix)).await);
+            return Ok(());
+        },
+    };
 
-    {
+    let (channel_map_lock, pool) = {
         let data_read = ctx.data.read().await;
-        let channel_map_lock = data_read.get::<ChannelRegistry>().expect("Unable to read channel map").clone();
+        (
+            data_read.get::<ChannelRegistry>().cloned().expect("Expected ChannelRegistry in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    let linked = match channel_map_lock.read().await.get(&guild_id.0) {
+        Some(links) => links.iter().any(|link| link.channel_id == channel_id.0),
+        None => false,
+    };
+    if linked {
+        if let Err(why) = db::run(&pool, move |pool| delete_channel_link(pool, guild_id.0, channel_id.0)).await {
+            println!("Error removing channel link: {:?}", why);
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        }
         let mut channel_map = channel_map_lock.write().await;
-        channel_map.remove(&guild_id.0);
+        if let Some(links) = channel_map.get_mut(&guild_id.0) {
+            links.retain(|link| link.channel_id != channel_id.0);
+            if links.is_empty() {
+                channel_map.remove(&guild_id.0);
+            }
+        }
+    }
+    if linked {
+        check_msg(msg.channel_id.say(&ctx.http, "I see how it is, no one wants me to speak (┛ಠ_ಠ)┛彡┻━┻").await);
+    } else {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("{} isn't linked, see {}links", channel_id.mention(), prefix)).await);
+    }
+    Ok(())
+}
+
+#[command]
+async fn links(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+
+    let channel_map_lock = ctx.data.read().await
+        .get::<ChannelRegistry>().cloned().expect("Expected ChannelRegistry in TypeMap.");
+    let lines: Vec<String> = match channel_map_lock.read().await.get(&guild_id.0) {
+        Some(links) => links.iter()
+            .map(|link| match link.voice_channel_id {
+                Some(voice_channel_id) => format!("> {} → {}\n", ChannelId(link.channel_id).mention(), ChannelId(voice_channel_id).mention()),
+                None => format!("> {}\n", ChannelId(link.channel_id).mention()),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    if lines.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("No channels are linked, use {}link in the one I should read", prefix)).await);
+        return Ok(());
+    }
+    let mut response = "Reading messages from:\n".to_string();
+    response.push_str(&lines.concat());
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+/// Toggles reading the text chat of the voice channel the bot is in, on top
+/// of the linked channels.
+#[command]
+async fn voicechat(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let enable = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            let state = if guild_config(ctx, guild_id).await.read_voice_chat { "on" } else { "off" };
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Reading voice chat is {0}, use {1}voicechat on or {1}voicechat off", state, prefix)).await);
+            return Ok(());
+        },
+    };
+
+    if update_guild_config(ctx, guild_id, |config| config.read_voice_chat = enable).await.is_err() {
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
     }
-    check_msg(msg.channel_id.say(&ctx.http, "I see how it is, no one wants me to speak (┛ಠ_ಠ)┛彡┻━┻").await);
+    let response = if enable {
+        "I'll also read the chat of the voice channel I'm in"
+    } else {
+        "I'll only read linked channels"
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
     Ok(())
 }
@@ -1,5 +1,17 @@
+pub mod announce;
+pub mod cache;
+pub mod config;
+pub mod default_voice;
 pub mod link;
 pub mod join;
 pub mod leave;
+pub mod length;
+pub mod permissions;
+pub mod presence;
+pub mod provider;
+pub mod queue;
+pub mod rate_limit;
+pub mod say_as;
 pub mod sound;
 pub mod user;
+pub mod voices;
@@ -0,0 +1,163 @@
+use crate::check_msg;
+use crate::db::{self, Database, SAVE_FAILED};
+use crate::permissions::{LOCKED_COMMANDS, PermissionLevel, command_keys, default_level, find_role};
+use crate::preferences::{
+    CommandPermissions,
+    guild_config,
+    update_guild_config,
+    save_command_permission,
+    delete_command_permission,
+    command_prefix,
+};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+/// Changes who may run a command, e.g. `g/permissions play everyone` or
+/// `g/permissions say-as remove default`.
+#[command]
+async fn permissions(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let (overrides_lock, pool) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<CommandPermissions>().cloned().expect("Expected CommandPermissions in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    let usage = format!("Use {0}permissions {{command}} everyone|dj|admin|default, e.g. {0}permissions play everyone", prefix);
+
+    let words: Vec<String> = args.raw().map(|word| word.to_lowercase()).collect();
+    let (level, command) = match words.split_last() {
+        Some((level, command)) if !command.is_empty() => (level.to_string(), command.join(" ")),
+        Some(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, usage).await);
+            return Ok(());
+        },
+        None => {
+            let overrides = overrides_lock.read().await;
+            let mut lines: Vec<String> = match overrides.get(&guild_id.0) {
+                Some(commands) => commands.iter()
+                    .map(|(command, level)| format!("> {}{}: {} (default {})\n", prefix, command, level.as_str(), default_level(command).as_str()))
+                    .collect(),
+                None => Vec::new(),
+            };
+            lines.sort();
+            let response = if lines.is_empty() {
+                format!("Every command uses its default permissions\n{}", usage)
+            } else {
+                format!("Changed permissions:\n{}{}", lines.concat(), usage)
+            };
+            check_msg(msg.channel_id.say(&ctx.http, response).await);
+            return Ok(());
+        },
+    };
+
+    if !command_keys().contains(&command) {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("I don't have a {}{} command", prefix, command)).await);
+        return Ok(());
+    }
+    if LOCKED_COMMANDS.contains(&command.as_str()) {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("{}{} always needs Manage Server", prefix, command)).await);
+        return Ok(());
+    }
+    let level = match level.as_str() {
+        "default" => None,
+        level => match level.parse::<PermissionLevel>() {
+            Ok(level) => Some(level),
+            Err(_) => {
+                check_msg(msg.channel_id.say(&ctx.http, usage).await);
+                return Ok(());
+            },
+        },
+    };
+
+    {
+        let key = command.to_string();
+        let saved = match level {
+            Some(level) => db::run(&pool, move |pool| save_command_permission(pool, guild_id.0, &key, level)).await,
+            None => db::run(&pool, move |pool| delete_command_permission(pool, guild_id.0, &key)).await,
+        };
+        if let Err(why) = saved {
+            println!("Error saving command permission: {:?}", why);
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        }
+        let mut overrides = overrides_lock.write().await;
+        match level {
+            Some(level) => {
+                overrides.entry(guild_id.0).or_default().insert(command.to_string(), level);
+            },
+            None => {
+                if let Some(commands) = overrides.get_mut(&guild_id.0) {
+                    commands.remove(&command);
+                    if commands.is_empty() {
+                        overrides.remove(&guild_id.0);
+                    }
+                }
+            },
+        }
+    }
+    let level = level.unwrap_or_else(|| default_level(&command));
+    let who = match level {
+        PermissionLevel::Everyone => "everyone",
+        PermissionLevel::Dj => "people with Manage Server or the DJ role",
+        PermissionLevel::Admin => "people with Manage Server",
+    };
+    check_msg(msg.channel_id.say(&ctx.http, &format!("{}{} can now be used by {}", prefix, command, who)).await);
+    Ok(())
+}
+
+/// Sets the role that may control voice and change settings without
+/// Manage Server, e.g. `g/djrole @Gabby DJ` or `g/djrole clear`.
+#[command]
+async fn djrole(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    if args.is_empty() {
+        let role = match guild_config(ctx, guild_id).await.dj_role_id {
+            Some(role_id) => ctx.cache.role(guild_id, role_id as u64).await,
+            None => None,
+        };
+        let response = match role {
+            Some(role) => format!("The DJ role is {0}, change it with {1}djrole {{role}} or {1}djrole clear", role.name, prefix),
+            None => format!("There's no DJ role, pick one with {}djrole {{role}}", prefix),
+        };
+        check_msg(msg.channel_id.say(&ctx.http, response).await);
+        return Ok(());
+    }
+    if args.current().map(|arg| arg.eq_ignore_ascii_case("clear")) == Some(true) {
+        if update_guild_config(ctx, guild_id, |config| config.dj_role_id = None).await.is_err() {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        }
+        check_msg(msg.channel_id.say(&ctx.http, "DJ role cleared, only Manage Server can control me now").await);
+        return Ok(());
+    }
+
+    let role = find_role(ctx, guild_id, args.rest()).await;
+    let role = match role {
+        Some(role) => role,
+        None => {
+            check_msg(msg.channel_id.say(&ctx.http, "I can't find that role on this server").await);
+            return Ok(());
+        },
+    };
+    if update_guild_config(ctx, guild_id, |config| config.dj_role_id = Some(role.id.0 as i64)).await.is_err() {
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    check_msg(msg.channel_id.say(&ctx.http, &format!("{} can now control me and change my settings", role.name)).await);
+    Ok(())
+}
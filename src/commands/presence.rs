@@ -0,0 +1,114 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::models::guild_config::GuildConfig;
+use crate::preferences::{command_prefix, guild_config, update_guild_config};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+/// Toggles joining the voice channel of whoever speaks in the linked channel
+/// when the bot isn't in voice yet.
+#[command]
+async fn autojoin(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let enable = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            let state = if guild_config(ctx, guild_id).await.auto_join { "on" } else { "off" };
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Auto join is {0}, use {1}autojoin on or {1}autojoin off", state, prefix)).await);
+            return Ok(());
+        },
+    };
+
+    if update_guild_config(ctx, guild_id, |config| config.auto_join = enable).await.is_err() {
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    let response = if enable {
+        "I'll join your voice channel when you talk in the linked channel".to_string()
+    } else {
+        format!("I'll only join voice with {}join", prefix)
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+/// Sets how many minutes the bot stays in voice with nothing to play,
+/// 0 keeps it there until someone runs g/leave.
+#[command]
+async fn idle(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let (min, max) = GuildConfig::IDLE_TIMEOUT_RANGE;
+    let minutes = match args.single::<i32>() {
+        Ok(minutes) if minutes >= min && minutes <= max => minutes,
+        Ok(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("That needs a number of minutes between {} and {}", min, max)).await);
+            return Ok(());
+        },
+        Err(_) => {
+            let minutes = guild_config(ctx, guild_id).await.idle_timeout_mins;
+            check_msg(msg.channel_id.say(&ctx.http, &format!("{}, change it with {}idle {{minutes}}", describe_idle(minutes, &prefix), prefix)).await);
+            return Ok(());
+        },
+    };
+
+    if update_guild_config(ctx, guild_id, |config| config.idle_timeout_mins = minutes).await.is_err() {
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    check_msg(msg.channel_id.say(&ctx.http, describe_idle(minutes, &prefix)).await);
+    Ok(())
+}
+
+/// Toggles only reading messages from people in the bot's voice channel,
+/// so nobody talks into a call they aren't part of.
+#[command]
+async fn voiceonly(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let enable = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            let state = if guild_config(ctx, guild_id).await.voice_present_only { "on" } else { "off" };
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Voice only is {0}, use {1}voiceonly on or {1}voiceonly off", state, prefix)).await);
+            return Ok(());
+        },
+    };
+
+    if update_guild_config(ctx, guild_id, |config| config.voice_present_only = enable).await.is_err() {
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    let response = if enable {
+        "I'll only read messages from people in my voice channel"
+    } else {
+        "I'll read messages from everyone registered, in voice or not"
+    };
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+fn describe_idle(minutes: i32, prefix: &str) -> String {
+    if minutes > 0 {
+        format!("I leave voice after {} minutes without anything to say", minutes)
+    } else {
+        format!("I stay in voice until someone runs {}leave or everyone's gone", prefix)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::preferences::{command_prefix, guild_config, update_guild_config};
+use crate::tts::provider::TtsProviders;
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+#[command]
+async fn provider(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let providers = ctx.data.read().await
+        .get::<TtsProviders>().cloned().expect("Expected TtsProviders in TypeMap.");
+    let available = providers.ids().join(", ");
+
+    let provider_id = match args.single::<String>() {
+        Ok(id) => id.to_lowercase(),
+        Err(_) => {
+            let current = providers.for_guild(&guild_config(ctx, guild_id).await);
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Using {} for new voices, available: {}", current.id(), available)).await);
+            return Ok(());
+        },
+    };
+
+    if providers.get(&provider_id).is_none() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("I don't know that one, pick one of: {}", available)).await);
+        return Ok(());
+    }
+    if update_guild_config(ctx, guild_id, |config| config.tts_provider = Some(provider_id.to_string())).await.is_err() {
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    check_msg(msg.channel_id.say(&ctx.http, &format!("Now offering {} voices, use {}register to pick one", provider_id, prefix)).await);
+    Ok(())
+}
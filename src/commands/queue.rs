@@ -0,0 +1,62 @@
+use crate::check_msg;
+use crate::playback;
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    macros::command,
+};
+
+#[command]
+async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+
+    let (now_playing, upcoming) = playback::list(ctx, guild_id).await;
+    let now_playing = match now_playing {
+        Some(title) => title,
+        None => {
+            check_msg(msg.channel_id.say(&ctx.http, "Nothing is playing right now").await);
+            return Ok(());
+        },
+    };
+
+    let mut response = format!("Now playing: {}\n", now_playing);
+    for (index, title) in upcoming.iter().take(10).enumerate() {
+        response.push_str(&format!("> {}. {}\n", index + 1, title));
+    }
+    if upcoming.len() > 10 {
+        response.push_str(&format!("…and {} more", upcoming.len() - 10));
+    }
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+#[command]
+async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+
+    if playback::skip(ctx, guild_id).await {
+        check_msg(msg.channel_id.say(&ctx.http, "Skipped").await);
+    } else {
+        check_msg(msg.channel_id.say(&ctx.http, "Nothing to skip").await);
+    }
+    Ok(())
+}
+
+#[command]
+async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+
+    let count = playback::clear(ctx, guild_id).await;
+    check_msg(msg.channel_id.say(&ctx.http, &format!("Cleared {} queued clips", count)).await);
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use crate::check_msg;
+use crate::db::SAVE_FAILED;
+use crate::models::guild_config::GuildConfig;
+use crate::preferences::{command_prefix, guild_config, update_guild_config};
+use crate::rate_limit::RateLimitAction;
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+fn describe_limits(config: &GuildConfig) -> String {
+    let per_minute = |limit: i32| if limit > 0 {
+        format!("{} per minute", limit)
+    } else {
+        "unlimited".to_string()
+    };
+    let limits = config.rate_limits();
+    let overflow = match limits.action {
+        RateLimitAction::Drop => "dropped",
+        RateLimitAction::Coalesce => "held and read together",
+    };
+    format!(
+        "Messages per user: {}, for the whole server: {}. Messages over the limit are {}{}",
+        per_minute(config.user_rate_limit),
+        per_minute(config.guild_rate_limit),
+        overflow,
+        if config.rate_limit_notify { " and I'll say so" } else { "" },
+    )
+}
+
+/// Sets how many messages a minute get read, e.g. `g/ratelimit user 6`,
+/// `g/ratelimit guild 20`, `g/ratelimit action coalesce` or
+/// `g/ratelimit notify off`. A limit of 0 turns it off.
+#[command]
+async fn ratelimit(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let (min, max) = GuildConfig::RATE_LIMIT_RANGE;
+    let usage = format!("Use {0}ratelimit user|guild {{messages per minute}}, {0}ratelimit action drop|coalesce or {0}ratelimit notify on|off", prefix);
+    let setting = args.single::<String>().map(|setting| setting.to_lowercase());
+
+    let saved = match setting.as_deref() {
+        Ok(scope @ "user") | Ok(scope @ "guild") => {
+            let limit = match args.single::<i32>() {
+                Ok(limit) if limit >= min && limit <= max => limit,
+                _ => {
+                    check_msg(msg.channel_id.say(&ctx.http, &format!("That needs a number of messages per minute between {} and {}", min, max)).await);
+                    return Ok(());
+                },
+            };
+            let per_user = scope == "user";
+            update_guild_config(ctx, guild_id, |config| if per_user {
+                config.user_rate_limit = limit;
+            } else {
+                config.guild_rate_limit = limit;
+            }).await
+        },
+        Ok("action") => {
+            let action = match args.single::<String>().map(|action| action.parse::<RateLimitAction>()) {
+                Ok(Ok(action)) => action,
+                _ => {
+                    check_msg(msg.channel_id.say(&ctx.http, usage).await);
+                    return Ok(());
+                },
+            };
+            update_guild_config(ctx, guild_id, |config| config.rate_limit_action = action.as_str().to_string()).await
+        },
+        Ok("notify") => {
+            let notify = match args.single::<String>().as_deref() {
+                Ok("on") => true,
+                Ok("off") => false,
+                _ => {
+                    check_msg(msg.channel_id.say(&ctx.http, usage).await);
+                    return Ok(());
+                },
+            };
+            update_guild_config(ctx, guild_id, |config| config.rate_limit_notify = notify).await
+        },
+        Ok(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, usage).await);
+            return Ok(());
+        },
+        Err(_) => {
+            let config = guild_config(ctx, guild_id).await;
+            check_msg(msg.channel_id.say(&ctx.http, &format!("{}\n{}", describe_limits(&config), usage)).await);
+            return Ok(());
+        },
+    };
+    let config = match saved {
+        Ok(config) => config,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        },
+    };
+    check_msg(msg.channel_id.say(&ctx.http, describe_limits(&config)).await);
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use crate::check_msg;
+use crate::paginate;
+use crate::db::{self, Database, SAVE_FAILED};
+use crate::normalize::Pronunciation;
+use crate::preferences::{command_prefix, Pronunciations, save_pronunciation, delete_pronunciation};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+
+const MAX_ENTRIES: usize = 200;
+const MAX_WORD_LENGTH: usize = 50;
+const MAX_REPLACEMENT_LENGTH: usize = 100;
+
+/// Teaches the guild's dictionary how to say a word, e.g.
+/// `g/say-as gif jif` or `g/say-as "Wo Long" woe long`.
+#[command("say-as")]
+#[sub_commands(say_as_list, say_as_remove)]
+async fn say_as(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let word = match args.quoted().single::<String>() {
+        Ok(word) => word.trim().to_lowercase(),
+        Err(_) => String::new(),
+    };
+    let replacement = args.rest().trim().to_string();
+    if word.is_empty() || replacement.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("Use {0}say-as {{word}} {{how to say it}}, {0}say-as list or {0}say-as remove {{word}}", prefix)).await);
+        return Ok(());
+    }
+    if word.len() > MAX_WORD_LENGTH || replacement.len() > MAX_REPLACEMENT_LENGTH {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("Keep words under {} and replacements under {} characters", MAX_WORD_LENGTH, MAX_REPLACEMENT_LENGTH)).await);
+        return Ok(());
+    }
+
+    let (dictionaries_lock, pool) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<Pronunciations>().cloned().expect("Expected Pronunciations in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    let (known, entries) = match dictionaries_lock.read().await.get(&guild_id.0) {
+        Some(dictionary) => (dictionary.iter().any(|entry| entry.word == word), dictionary.len()),
+        None => (false, 0),
+    };
+    if !known && entries >= MAX_ENTRIES {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("The dictionary is full ({} words), remove some first", MAX_ENTRIES)).await);
+        return Ok(());
+    }
+
+    let pronunciation = Pronunciation {
+        word: word.to_string(),
+        replacement: replacement.to_string(),
+    };
+    let saved = pronunciation.clone();
+    if let Err(why) = db::run(&pool, move |pool| save_pronunciation(pool, guild_id.0, &saved)).await {
+        println!("Error saving pronunciation: {:?}", why);
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
+    }
+    let mut dictionaries = dictionaries_lock.write().await;
+    let dictionary = dictionaries.entry(guild_id.0).or_default();
+    match dictionary.iter().position(|entry| entry.word == word) {
+        Some(index) => dictionary[index] = pronunciation,
+        None => dictionary.push(pronunciation),
+    }
+    drop(dictionaries);
+    check_msg(msg.channel_id.say(&ctx.http, &format!("Got it, I'll say {} as \"{}\"", word, replacement)).await);
+    Ok(())
+}
+
+#[command("list")]
+async fn say_as_list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let page = args.single::<usize>().unwrap_or(1).max(1);
+
+    let dictionaries_lock = ctx.data.read().await
+        .get::<Pronunciations>().cloned().expect("Expected Pronunciations in TypeMap.");
+    let lines: Vec<String> = match dictionaries_lock.read().await.get(&guild_id.0) {
+        Some(dictionary) => dictionary.iter()
+            .map(|entry| format!("> {} → {}\n", entry.word, entry.replacement))
+            .collect(),
+        None => Vec::new(),
+    };
+    if lines.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("The dictionary is empty, add words with {}say-as {{word}} {{how to say it}}", prefix)).await);
+        return Ok(());
+    }
+
+    let pages = paginate(&lines, 1800);
+    let page = page.min(pages.len());
+    let mut response = format!("Pronunciations, page {} of {}:\n", page, pages.len());
+    response.push_str(&pages[page - 1]);
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+#[command("remove")]
+async fn say_as_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let word = match args.quoted().single::<String>() {
+        Ok(word) => word.trim().to_lowercase(),
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Use {}say-as remove {{word}}", prefix)).await);
+            return Ok(());
+        },
+    };
+
+    let (dictionaries_lock, pool) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<Pronunciations>().cloned().expect("Expected Pronunciations in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    let known = match dictionaries_lock.read().await.get(&guild_id.0) {
+        Some(dictionary) => dictionary.iter().any(|entry| entry.word == word),
+        None => false,
+    };
+    if known {
+        let removed = word.to_string();
+        if let Err(why) = db::run(&pool, move |pool| delete_pronunciation(pool, guild_id.0, &removed)).await {
+            println!("Error removing pronunciation: {:?}", why);
+            check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+            return Ok(());
+        }
+        if let Some(dictionary) = dictionaries_lock.write().await.get_mut(&guild_id.0) {
+            dictionary.retain(|entry| entry.word != word);
+        }
+        check_msg(msg.channel_id.say(&ctx.http, &format!("Forgot how to say {}", word)).await);
+    } else {
+        check_msg(msg.channel_id.say(&ctx.http, "That word isn't in the dictionary").await);
+    }
+    Ok(())
+}
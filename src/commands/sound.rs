@@ -1,6 +1,6 @@
 use crate::check_msg;
 use crate::VoiceManager;
-use serenity::voice;
+use crate::playback::{self, Track, TrackInput};
 use serenity::prelude::*;
 use serenity::model::prelude::*;
 use serenity::framework::standard::{
@@ -19,21 +19,14 @@ async fn jump_scare(ctx: &Context, msg: &Message) -> CommandResult {
             return Ok(());
         },
     };
-    let data_read = ctx.data.read().await;
-    let manager_lock = data_read.get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
-    let mut manager = manager_lock.lock().await;
-    if let Some(handler) = manager.get_mut(guild_id) {
-        let source = match voice::ffmpeg("./trex.ogg").await {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
-
-                check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg").await);
-
-                return Ok(());
-            },
-        };
-        handler.play(source);
+    let manager_lock = ctx.data.read().await
+        .get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
+    let in_voice = manager_lock.lock().await.get(guild_id).is_some();
+    if in_voice {
+        playback::enqueue(ctx, guild_id, Track {
+            title: "Jump scare".to_string(),
+            input: TrackInput::File("./trex.ogg".to_string()),
+        }).await;
     }
 
     Ok(())
@@ -1,8 +1,18 @@
 use crate::check_msg;
-use crate::UserPreferences;
-use crate::UserPref;
-use crate::tts::google_tts::{list_voices};
-use crate::tts::models::Voice;
+use crate::db::{self, Database, SAVE_FAILED};
+use crate::preferences::{
+    UserPreferences,
+    UserPref,
+    guild_provider,
+    update_user_pref,
+    save_user_preference,
+    delete_user_preference,
+    command_prefix,
+};
+use crate::tts::{
+    catalog::{VoiceCatalog, VoiceLookup},
+    provider::AudioSettings,
+};
 use serenity::prelude::*;
 use serenity::model::prelude::*;
 use serenity::framework::standard::{
@@ -13,52 +23,59 @@ use serenity::framework::standard::{
 
 #[command]
 pub async fn register(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let voices = list_voices().await?;
+    let prefix = command_prefix(ctx, msg.guild_id).await;
     let voice_name = match args.single::<String>() {
         Ok(url) => url,
         Err(_) => {
-            let mut response = String::from("You need to select a voice (use !register {voice}), here is everything I can do:\n");
-            for voice in voices.iter() {
-                if voice.language_codes.len() > 0 {
-                    let language_code = &voice.language_codes[0];
-                    if language_code == "en-US" || language_code == "en-GB" {
-                        response.push_str(&format!("> {}: {}\n", voice.ssml_gender.to_lowercase(), voice.name));
-                    }
-                }
-            }
-            response.push_str("{voice} is something like en-US-Wavenet-I -- you do not need to provide the gender part");
+            let response = format!("You need to select a voice (use {0}register {{voice}}), browse them with {0}voices [language] [gender] [type], e.g. {0}voices en-GB female wavenet\n\
+                {{voice}} is something like en-US-Wavenet-I -- you do not need to provide the gender part", prefix);
             check_msg(msg.channel_id.say(&ctx.http, response).await);
 
             return Ok(());
         },
     };
-    let mut voice_iter = voices.into_iter();
-    if let Some(voice) = voice_iter.find(|x| x.name == voice_name.trim()) {
-        let user_preferences_lock = {
+    let provider = guild_provider(ctx, msg.guild_id).await;
+    let catalog = ctx.data.read().await
+        .get::<VoiceCatalog>().cloned().expect("Expected VoiceCatalog in TypeMap.");
+    let lookup = match catalog.lookup(provider.id(), &voice_name).await {
+        Ok(lookup) => lookup,
+        Err(why) => {
+            println!("Err listing voices: {}", why);
+            check_msg(msg.channel_id.say(&ctx.http, why.user_message(&prefix)).await);
+            return Ok(());
+        },
+    };
+    if let VoiceLookup::Found(voice) = lookup {
+        let (user_preferences_lock, pool) = {
             let data_read = ctx.data.read().await;
-            data_read.get::<UserPreferences>().expect("Unable to read channel ID").clone()
+            (
+                data_read.get::<UserPreferences>().expect("Unable to read channel ID").clone(),
+                data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+            )
         };
 
         {
-            let actual_voice = Voice {
-                language_code: voice.language_codes[0].to_string(),
-                name: voice.name.to_string(),
-                ssml_gender: voice.ssml_gender.to_string(),
-            };
-            let actual_voice2 = Voice {
-                language_code: voice.language_codes[0].to_string(),
-                name: voice.name.to_string(),
-                ssml_gender: voice.ssml_gender.to_string(),
-            };
+            let actual_voice = voice.to_voice();
             println!("Registering voice: {:?}", actual_voice);
-            let mut user_preferences = user_preferences_lock.write().await;
-            *user_preferences.entry(msg.author.id.0).or_insert(UserPref {
-                voice: actual_voice
-            }) = UserPref {
-                voice: actual_voice2
+            // Switching voices keeps the rest of the user's settings
+            let pref = match user_preferences_lock.read().await.get(&msg.author.id.0) {
+                Some(existing) => UserPref {
+                    voice: actual_voice,
+                    ..existing.clone()
+                },
+                None => UserPref::new(actual_voice),
             };
+            let (user_id, saved) = (msg.author.id.0, pref.clone());
+            if let Err(why) = db::run(&pool, move |pool| save_user_preference(pool, user_id, &saved)).await {
+                println!("Error saving voice preference: {:?}", why);
+                check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+                return Ok(());
+            }
+            user_preferences_lock.write().await.insert(msg.author.id.0, pref);
         }
         check_msg(msg.channel_id.say(&ctx.http, "Voice registered!").await);
+    } else if let VoiceLookup::DidYouMean(suggestion) = lookup {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("I don't know that voice, did you mean {}?", suggestion)).await);
     } else {
         check_msg(msg.channel_id.say(&ctx.http, "I don't know that voice :7").await);
     }
@@ -67,15 +84,105 @@ pub async fn register(ctx: &Context, msg: &Message, mut args: Args) -> CommandRe
 
 #[command]
 pub async fn unregister(ctx: &Context, msg: &Message, mut _args: Args) -> CommandResult {
-    let user_preferences_lock = {
+    let (user_preferences_lock, pool) = {
         let data_read = ctx.data.read().await;
-        data_read.get::<UserPreferences>().expect("Unable to read channel ID").clone()
+        (
+            data_read.get::<UserPreferences>().expect("Unable to read channel ID").clone(),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
     };
-    
-    {
-        let mut user_preferences = user_preferences_lock.write().await;
-        user_preferences.remove(&msg.author.id.0);
+
+    let user_id = msg.author.id.0;
+    if let Err(why) = db::run(&pool, move |pool| delete_user_preference(pool, user_id)).await {
+        println!("Error removing voice preference: {:?}", why);
+        check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await);
+        return Ok(());
     }
+    user_preferences_lock.write().await.remove(&msg.author.id.0);
     check_msg(msg.channel_id.say(&ctx.http, "Done! I'll leave your messages alone").await);
     Ok(())
 }
+
+#[command]
+pub async fn ssml(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let enable = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Use {0}ssml on or {0}ssml off. With it on, *word* is emphasized and ... makes me pause", prefix)).await);
+            return Ok(());
+        },
+    };
+
+    match update_user_pref(ctx, msg.author.id, |pref| pref.ssml = enable).await {
+        Ok(Some(_)) if enable => check_msg(msg.channel_id.say(&ctx.http, "SSML on, *emphasis* and ... pauses will be spoken").await),
+        Ok(Some(_)) => check_msg(msg.channel_id.say(&ctx.http, "SSML off, I'll read your messages as plain text").await),
+        Ok(None) => check_msg(msg.channel_id.say(&ctx.http, &format!("Register a voice first with {}register", prefix)).await),
+        Err(_) => check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await),
+    }
+    Ok(())
+}
+
+type AudioSetter = fn(&mut AudioSettings, f64);
+
+fn describe_voice(pref: &UserPref) -> String {
+    format!(
+        "{} at rate {}, pitch {}, gain {} dB",
+        pref.voice.name,
+        pref.audio.speaking_rate,
+        pref.audio.pitch,
+        pref.audio.volume_gain_db,
+    )
+}
+
+#[command("voice")]
+pub async fn voice_settings(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let usage = format!("Use {0}voice rate|pitch|gain {{value}} or {0}voice reset, e.g. {0}voice rate 1.2", prefix);
+    let setting = args.single::<String>().map(|setting| setting.to_lowercase());
+
+    let (range, apply): ((f64, f64), AudioSetter) = match setting.as_deref() {
+        Ok("rate") => (AudioSettings::SPEAKING_RATE_RANGE, |audio, value| audio.speaking_rate = value),
+        Ok("pitch") => (AudioSettings::PITCH_RANGE, |audio, value| audio.pitch = value),
+        Ok("gain") => (AudioSettings::VOLUME_GAIN_DB_RANGE, |audio, value| audio.volume_gain_db = value),
+        Ok("reset") => {
+            match update_user_pref(ctx, msg.author.id, |pref| pref.audio = AudioSettings::default()).await {
+                Ok(Some(pref)) => check_msg(msg.channel_id.say(&ctx.http, &format!("Back to {}", describe_voice(&pref))).await),
+                Ok(None) => check_msg(msg.channel_id.say(&ctx.http, &format!("Register a voice first with {}register", prefix)).await),
+                Err(_) => check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await),
+            }
+            return Ok(());
+        },
+        Ok(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, usage).await);
+            return Ok(());
+        },
+        Err(_) => {
+            let user_preferences_lock = ctx.data.read().await
+                .get::<UserPreferences>().cloned().expect("Expected UserPreferences in TypeMap.");
+            let response = match user_preferences_lock.read().await.get(&msg.author.id.0) {
+                Some(pref) => format!("You're using {}\n{}", describe_voice(pref), usage),
+                None => format!("Register a voice first with {}register", prefix),
+            };
+            check_msg(msg.channel_id.say(&ctx.http, response).await);
+            return Ok(());
+        },
+    };
+
+    let (min, max) = range;
+    let value = match args.single::<f64>() {
+        Ok(value) if value.is_finite() && value >= min && value <= max => value,
+        _ => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("That needs a value between {} and {}", min, max)).await);
+            return Ok(());
+        },
+    };
+
+    match update_user_pref(ctx, msg.author.id, |pref| apply(&mut pref.audio, value)).await {
+        Ok(Some(pref)) => check_msg(msg.channel_id.say(&ctx.http, &format!("Now using {}", describe_voice(&pref))).await),
+        Ok(None) => check_msg(msg.channel_id.say(&ctx.http, &format!("Register a voice first with {}register", prefix)).await),
+        Err(_) => check_msg(msg.channel_id.say(&ctx.http, SAVE_FAILED).await),
+    }
+    Ok(())
+}
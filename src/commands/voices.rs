@@ -0,0 +1,175 @@
+use crate::{check_msg, mention_names, normalize_for_guild, paginate, read_out};
+use crate::VoiceManager;
+use crate::normalize::NormalizeOptions;
+use crate::preferences::{command_prefix, UserPref, guild_config, guild_provider};
+use crate::rate_limit::{Admission, Limits, RateLimitAction, RateLimiter};
+use crate::tts::{
+    catalog::{VoiceCatalog, VoiceLookup},
+    models::VoiceDescriptor,
+};
+use serenity::prelude::*;
+use serenity::model::prelude::*;
+use serenity::framework::standard::{
+    CommandResult,
+    Args,
+    macros::command,
+};
+use std::time::Instant;
+
+#[derive(Default)]
+struct VoiceFilter {
+    language: Option<String>,
+    gender: Option<String>,
+    voice_type: Option<String>,
+}
+
+impl VoiceFilter {
+    fn matches(&self, voice: &VoiceDescriptor) -> bool {
+        let language_matches = match &self.language {
+            Some(language) => voice.language_codes.iter().any(|code| code.to_lowercase().starts_with(language.as_str())),
+            None => true,
+        };
+        let gender_matches = match &self.gender {
+            Some(gender) => voice.ssml_gender.to_lowercase() == *gender,
+            None => true,
+        };
+        let type_matches = match &self.voice_type {
+            Some(voice_type) => voice.voice_type().to_lowercase() == *voice_type,
+            None => true,
+        };
+        language_matches && gender_matches && type_matches
+    }
+}
+
+/// Lists voices, optionally filtered. Arguments can be given in any order:
+/// a gender, a voice type, a page number and anything else is taken as a
+/// language code prefix.
+#[command]
+async fn voices(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let provider = guild_provider(ctx, msg.guild_id).await;
+    let catalog = ctx.data.read().await
+        .get::<VoiceCatalog>().cloned().expect("Expected VoiceCatalog in TypeMap.");
+    let voices = match catalog.voices(provider.id()).await {
+        Ok(voices) => voices,
+        Err(why) => {
+            println!("Err listing voices: {}", why);
+            check_msg(msg.channel_id.say(&ctx.http, why.user_message(&prefix)).await);
+            return Ok(());
+        },
+    };
+
+    let mut filter = VoiceFilter::default();
+    let mut page = 1;
+    for arg in args.iter::<String>().filter_map(Result::ok) {
+        let arg = arg.to_lowercase();
+        if let Ok(number) = arg.parse::<usize>() {
+            page = number.max(1);
+        } else if arg == "male" || arg == "female" || arg == "neutral" {
+            filter.gender = Some(arg);
+        } else if voices.iter().any(|voice| voice.voice_type().to_lowercase() == arg) {
+            filter.voice_type = Some(arg);
+        } else {
+            filter.language = Some(arg);
+        }
+    }
+
+    let lines: Vec<String> = voices.iter()
+        .filter(|voice| filter.matches(voice))
+        .map(|voice| format!("> {}: {} ({})\n", voice.ssml_gender.to_lowercase(), voice.name, voice.language_codes.join(", ")))
+        .collect();
+    if lines.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("No voices match that, try {0}voices en or {0}voices female wavenet", prefix)).await);
+        return Ok(());
+    }
+
+    let pages = paginate(&lines, 1800);
+    let page = page.min(pages.len());
+    let mut response = format!("{} voices, page {} of {}:\n", lines.len(), page, pages.len());
+    response.push_str(&pages[page - 1]);
+    if page < pages.len() {
+        response.push_str(&format!("Add {} to the command for the next page. ", page + 1));
+    }
+    response.push_str(&format!("Pick one with {}register {{voice}}", prefix));
+    check_msg(msg.channel_id.say(&ctx.http, response).await);
+    Ok(())
+}
+
+/// Plays a sample of a voice in the current voice channel, without touching
+/// anyone's registration.
+#[command]
+async fn preview(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(v) => v,
+        None => return Ok(())
+    };
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    let voice_name = match args.single::<String>() {
+        Ok(name) => name,
+        Err(_) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Use {0}preview {{voice}} [text], e.g. {0}preview en-GB-Wavenet-A Hello there", prefix)).await);
+            return Ok(());
+        },
+    };
+
+    let (catalog, manager_lock, rate_limiter_lock) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<VoiceCatalog>().cloned().expect("Expected VoiceCatalog in TypeMap."),
+            data_read.get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap."),
+            data_read.get::<RateLimiter>().cloned().expect("Expected RateLimiter in TypeMap."),
+        )
+    };
+    if manager_lock.lock().await.get(guild_id).is_none() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("Not in a voice channel, use {}join first", prefix)).await);
+        return Ok(());
+    }
+
+    let provider = guild_provider(ctx, Some(guild_id)).await;
+    let voice = match catalog.lookup(provider.id(), &voice_name).await {
+        Ok(VoiceLookup::Found(voice)) => voice,
+        Ok(VoiceLookup::DidYouMean(suggestion)) => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("I don't know that voice, did you mean {}?", suggestion)).await);
+            return Ok(());
+        },
+        Ok(VoiceLookup::Unknown) => {
+            check_msg(msg.channel_id.say(&ctx.http, "I don't know that voice :7").await);
+            return Ok(());
+        },
+        Err(why) => {
+            println!("Err listing voices: {}", why);
+            check_msg(msg.channel_id.say(&ctx.http, why.user_message(&prefix)).await);
+            return Ok(());
+        },
+    };
+
+    let config = guild_config(ctx, guild_id).await;
+    let text = match args.rest().trim() {
+        "" => format!("Hi, I'm {}. This is what I sound like.", voice.name),
+        text => {
+            let names = mention_names(ctx, msg, guild_id).await;
+            normalize_for_guild(ctx, guild_id, text, &names, &NormalizeOptions::default()).await
+        },
+    };
+    if text.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, "There's nothing in that I can read out").await);
+        return Ok(());
+    }
+    // Previews count towards the rate limits, but can't be held for later
+    let limits = Limits {
+        action: RateLimitAction::Drop,
+        ..config.rate_limits()
+    };
+    let admission = rate_limiter_lock.lock().await
+        .admit(guild_id.0, msg.author.id.0, text, &limits, Instant::now());
+    let text = match admission {
+        Admission::Allowed(text) => text,
+        _ => {
+            check_msg(msg.channel_id.say(&ctx.http, &format!("Slow down {}, try the preview again in a bit", msg.author.mention())).await);
+            return Ok(());
+        },
+    };
+
+    let prefs = UserPref::new(voice.to_voice());
+    read_out(ctx, msg, guild_id, &prefs, &text, &format!("Preview of {}", voice.name)).await
+}
@@ -0,0 +1,42 @@
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
+use serenity::prelude::TypeMapKey;
+use std::error::Error;
+
+embed_migrations!();
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+pub type DbResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// What to reply when a change couldn't be stored, it isn't applied then.
+pub const SAVE_FAILED: &str = "I couldn't save that, please try again in a bit";
+
+pub struct Database;
+
+impl TypeMapKey for Database {
+    type Value = DbPool;
+}
+
+pub fn establish_pool(database_url: &str) -> DbResult<DbPool> {
+    let manager = ConnectionManager::<PgConnection>::new(database_url);
+    let pool = Pool::builder().build(manager)?;
+    Ok(pool)
+}
+
+/// Brings the database schema up to date, applying any pending migrations
+/// from the `migrations` directory that were embedded at compile time.
+pub fn run_migrations(pool: &DbPool) -> DbResult<()> {
+    let conn = pool.get()?;
+    embedded_migrations::run(&conn)?;
+    Ok(())
+}
+
+/// Runs database work on tokio's blocking pool, diesel's connections are
+/// synchronous and would otherwise stall the worker running the command.
+pub async fn run<T, F>(pool: &DbPool, work: F) -> DbResult<T>
+    where F: FnOnce(&DbPool) -> DbResult<T> + Send + 'static,
+          T: Send + 'static
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || work(&pool)).await?
+}
@@ -1,18 +1,49 @@
 extern crate dotenv;
 extern crate base64_stream;
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
+#[macro_use]
+extern crate lazy_static;
 
 mod commands;
+mod db;
+#[allow(non_local_definitions)]
+mod models;
+mod normalize;
+mod permissions;
+mod playback;
+mod preferences;
+mod presence;
+mod rate_limit;
+#[allow(non_local_definitions)]
+mod schema;
 mod tts;
 
 use tts::{
-    google_tts::*,
-    models::Voice,
+    cache::AudioCache,
+    catalog::VoiceCatalog,
+    clip::SpeechClip,
+    error::TtsError,
+    espeak::EspeakTts,
+    google_tts::GoogleTts,
+    provider::{SpeechRequest, TtsProviders},
+    ssml::to_ssml,
 };
+use db::Database;
+use models::guild_config::GuildConfig;
+use normalize::{channel_mention_ids, limit_length, normalize, MentionNames, NormalizeOptions};
+use playback::{PlaybackQueues, Track, TrackInput};
+use permissions::PERMITTED_CHECK;
+use preferences::{ChannelLink, ChannelRegistry, CommandPermissions, GuildConfigs, Pronunciations, UserPref, UserPreferences, command_prefix, guild_config, speaker_pref};
+use presence::VoiceActivity;
+use rate_limit::{Admission, RateLimiter};
 
-use regex::Regex;
 use dotenv::dotenv;
-use std::{env, sync::Arc};
+use std::{collections::HashMap, env, path::PathBuf, sync::Arc, time::{Duration, Instant}};
 use serenity::client::bridge::voice::ClientVoiceManager;
+use serenity::http::Http;
 use serenity::{client::Context, prelude::Mutex};
 use serenity::{
     async_trait,
@@ -21,30 +52,41 @@ use serenity::{
         StandardFramework,
         standard::{
             CommandResult,
-            macros::{group},
+            macros::{group, hook},
             macros::command,
             Args,
+            DispatchError,
+            Reason,
         },
     },
-    model::{channel::Message, gateway::Ready},
+    model::{channel::Message, gateway::Ready, id::{ChannelId, GuildId, UserId}, voice::VoiceState},
     Result as SerenityResult,
-    voice,
     prelude::*,
 };
-use std::fs::File;
-use std::io::prelude::*;
-use std::{collections::HashMap};
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, time::delay_for};
 
 use commands::{
+    announce::*,
+    cache::*,
+    config::*,
+    default_voice::*,
     join::*,
     leave::*,
+    length::*,
+    permissions::*,
     link::*,
+    presence::*,
+    provider::*,
+    queue::*,
+    rate_limit::*,
+    say_as::*,
     sound::*,
     user::*,
+    voices::*,
 };
 
 #[group]
+#[checks(Permitted)]
 #[commands(
     deafen,
     undeafen,
@@ -52,53 +94,87 @@ use commands::{
     unmute,
     join,
     leave,
+    autojoin,
+    idle,
+    voiceonly,
     link,
     unlink,
+    links,
+    voicechat,
     register,
     unregister,
+    voices,
+    preview,
+    say_as,
+    ssml,
+    voice_settings,
+    provider,
+    defaultvoice,
+    readeveryone,
+    announce,
+    length,
+    ratelimit,
+    permissions,
+    djrole,
+    config,
     jump_scare,
-    play
+    play,
+    queue,
+    skip,
+    clear,
+    cache
 )]
 struct General;
 struct VoiceManager;
-struct ChannelRegistry;
-struct UserPreferences;
+struct LastSpeakers;
 struct Handler;
 
-impl TypeMapKey for ChannelRegistry {
-    type Value = Arc<RwLock<HashMap<u64, u64>>>;
-}
-
-struct UserPref {
-    voice: Voice,
-}
-
-impl TypeMapKey for UserPreferences {
-    type Value = Arc<RwLock<HashMap<u64, UserPref>>>;
-}
-
 impl TypeMapKey for VoiceManager {
     type Value = Arc<Mutex<ClientVoiceManager>>;
 }
 
-fn clean_message(msg: &Message) -> String {
-    let mut final_str = String::from(&msg.content);
-    // Filter any URLs
-    let re = Regex::new(r"((\w+://)[-a-zA-Z0-9:@;?&=/%\+\.\*!'\(\),\$_\{\}\^~\[\]`#|]+)").unwrap();
-    final_str = re.replace_all(&final_str, "").to_string();
-
-    // Filter channels
-    let re = Regex::new(r"(<#[0-9]*>)").unwrap();
-    final_str = re.replace_all(&final_str, "").to_string();
+/// Who spoke last in each guild and when, so consecutive messages from the
+/// same person aren't all announced.
+impl TypeMapKey for LastSpeakers {
+    type Value = Arc<Mutex<HashMap<u64, (UserId, Instant)>>>;
+}
 
-    // Filter mentions
+/// Looks up the names to speak for everything mentioned in the message:
+/// nicknames for users, and the guild's role and channel names.
+async fn mention_names(ctx: &Context, msg: &Message, guild_id: GuildId) -> MentionNames {
+    let mut names = MentionNames::default();
     for mention in msg.mentions.iter() {
-        let id = mention.id;
-        let mention_str = format!("<@!{}>", id);
-        final_str = final_str.replace(&mention_str, &mention.name);
+        let name = match ctx.cache.member(guild_id, mention.id).await {
+            Some(member) => member.display_name().to_string(),
+            None => mention.name.to_string(),
+        };
+        names.users.insert(mention.id.0, name);
+    }
+    for role_id in msg.mention_roles.iter() {
+        if let Some(role) = ctx.cache.role(guild_id, *role_id).await {
+            names.roles.insert(role_id.0, role.name);
+        }
+    }
+    for channel_id in channel_mention_ids(&msg.content) {
+        if let Some(channel) = ctx.cache.guild_channel(channel_id).await {
+            names.channels.insert(channel_id, channel.name);
+        }
     }
+    names
+}
+
+async fn clean_message(ctx: &Context, msg: &Message, guild_id: GuildId, options: &NormalizeOptions) -> String {
+    let names = mention_names(ctx, msg, guild_id).await;
+    normalize_for_guild(ctx, guild_id, &msg.content, &names, options).await
+}
 
-    final_str
+/// Runs `normalize` with the guild's pronunciation dictionary.
+async fn normalize_for_guild(ctx: &Context, guild_id: GuildId, text: &str, names: &MentionNames, options: &NormalizeOptions) -> String {
+    let dictionaries_lock = ctx.data.read().await
+        .get::<Pronunciations>().cloned().expect("Expected Pronunciations in TypeMap.");
+    let dictionaries = dictionaries_lock.read().await;
+    let dictionary = dictionaries.get(&guild_id.0).map(Vec::as_slice).unwrap_or(&[]);
+    normalize(text, names, dictionary, options)
 }
 
 #[async_trait]
@@ -108,40 +184,51 @@ impl EventHandler for Handler {
     }
 
     async fn message(&self, ctx: Context, msg: Message) {
-        if (msg.author.name == "Gabby") {
+        if msg.author.name == "Gabby" {
             return
         }
-        if (msg.content == "bitch") {
+        // DMs don't have a config, they get the defaults
+        let config = match msg.guild_id {
+            Some(guild_id) => guild_config(&ctx, guild_id).await,
+            None => GuildConfig::new(0),
+        };
+        if config.canned_replies && msg.content == "bitch" {
             check_msg(msg.channel_id.say(&ctx.http, "Excuse me?! Go fuck yourself").await);
             return
         }
-        if (msg.content.starts_with("g/")) {
+        if msg.content.starts_with(config.prefix()) {
             return
         }
-        if (msg.content.contains("crumpets")) {
+        // Commands can also start by mentioning the bot
+        let bot_id = ctx.cache.current_user_id().await;
+        if msg.content.starts_with(&format!("<@{}>", bot_id)) || msg.content.starts_with(&format!("<@!{}>", bot_id)) {
+            return
+        }
+        if config.canned_replies && msg.content.contains("crumpets") {
             check_msg(msg.channel_id.say(&ctx.http, "Crumpets were buttered").await);
             return
         }
 
-        let guild_id;
-        match msg.guild_id {
-            Some(v) => guild_id = v,
+        let guild_id = match msg.guild_id {
+            Some(v) => v,
             None => return
         };
-        let data_read = ctx.data.read().await;
-        let channel_map_lock = data_read.get::<ChannelRegistry>().expect("Unable to read channel mappings").clone();
-        let channel_map = channel_map_lock.read().await;
-        let channel_id: u64;
-        match channel_map.get(&guild_id.0) {
-            Some(v) => channel_id = *v,
-            None => return
+        let link = match preferences::channel_link(&ctx, guild_id, msg.channel_id).await {
+            Some(link) => link,
+            None => match voice_chat_link(&ctx, guild_id, &msg).await {
+                Some(link) => link,
+                None => return
+            },
         };
+        if let Err(why) = handle_tts_message(&ctx, &msg, &link).await {
+            println!("Err handling TTS message: {:?}", why);
+        }
+    }
 
-        // We return here early so we get rid of the lock sooner
-        if channel_id != msg.channel_id.0 {
-            return
+    async fn voice_state_update(&self, ctx: Context, guild_id: Option<GuildId>, _old: Option<VoiceState>, new: VoiceState) {
+        if let Some(guild_id) = guild_id {
+            presence::voice_state_changed(&ctx, guild_id, &new).await;
         }
-        let _ = handle_tts_message(&ctx, &msg).await;
     }
 }
 
@@ -151,13 +238,75 @@ async fn main() {
     println!("Starting Gabby…");
     let token = env::var("DISCORD_TOKEN")
         .expect("Expected a token in the environment");
-
+    let database_url = env::var("DATABASE_URL")
+        .expect("Expected a database url in the environment");
+
+    let pool = db::establish_pool(&database_url)
+        .expect("Err connecting to the database");
+    db::run_migrations(&pool)
+        .expect("Err running database migrations");
+    let user_preferences = preferences::load_user_preferences(&pool)
+        .expect("Err loading user preferences");
+    let channel_links = preferences::load_channel_links(&pool)
+        .expect("Err loading channel links");
+    let guild_configs = preferences::load_guild_configs(&pool)
+        .expect("Err loading guild configs");
+    let pronunciations = preferences::load_pronunciations(&pool)
+        .expect("Err loading pronunciations");
+    let command_permissions = preferences::load_command_permissions(&pool)
+        .expect("Err loading command permissions");
+
+    let mut providers = TtsProviders::new();
+    if let Some(google) = GoogleTts::from_env() {
+        providers.register(Arc::new(google));
+    }
+    if let Some(espeak) = EspeakTts::detect() {
+        providers.register(Arc::new(espeak));
+    }
+    if providers.is_empty() {
+        panic!("Expected at least one TTS provider to be configured");
+    }
+    // Without TTS_PROVIDER Google is preferred, then the first one by name
+    let default_provider = match env::var("TTS_PROVIDER") {
+        Ok(id) => id,
+        Err(_) if providers.get(GoogleTts::ID).is_some() => GoogleTts::ID.to_string(),
+        Err(_) => providers.ids()[0].to_string(),
+    };
+    if let Err(why) = providers.set_default(&default_provider) {
+        panic!("Err picking the default TTS provider from TTS_PROVIDER: {}", why);
+    }
+    println!("=> TTS providers: {}", providers.ids().join(", "));
+    let providers = Arc::new(providers);
+
+    let catalog = Arc::new(VoiceCatalog::new(providers.clone()));
+    catalog.refresh().await;
+    let refresh_minutes: u64 = env::var("VOICE_CATALOG_REFRESH_MINUTES").ok()
+        .and_then(|minutes| minutes.parse().ok())
+        .unwrap_or(360);
+    catalog.clone().spawn_refresh(Duration::from_secs(refresh_minutes * 60));
+
+    let cache_dir = env::var("TTS_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir().join("gabby-cache"));
+    let cache_size_mb: u64 = env::var("TTS_CACHE_SIZE_MB").ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(256);
+    let audio_cache = AudioCache::open(cache_dir, cache_size_mb * 1_048_576)
+        .expect("Err opening the audio cache");
+    println!("=> Loaded {} voices and {} linked channels", user_preferences.len(), channel_links.values().map(Vec::len).sum::<usize>());
+
+    let bot_id = Http::new_with_token(&token).get_current_user().await
+        .map(|user| user.id)
+        .expect("Err fetching the bot's user");
     let framework = StandardFramework::new()
         .configure(|c| c
-                   .prefix("g/"))
+                   .prefix("")
+                   .dynamic_prefix(guild_prefix)
+                   .on_mention(Some(bot_id)))
+        .on_dispatch_error(dispatch_error)
         .group(&GENERAL_GROUP);
 
-    let mut client = Client::new(&token)
+    let mut client = Client::builder(&token)
         .event_handler(Handler)
         .framework(framework)
         .await
@@ -169,8 +318,19 @@ async fn main() {
     {
         let mut data = client.data.write().await;
         data.insert::<VoiceManager>(Arc::clone(&client.voice_manager));
-        data.insert::<ChannelRegistry>(Arc::new(RwLock::new(HashMap::default())));
-        data.insert::<UserPreferences>(Arc::new(RwLock::new(HashMap::default())));
+        data.insert::<Database>(pool);
+        data.insert::<ChannelRegistry>(Arc::new(RwLock::new(channel_links)));
+        data.insert::<UserPreferences>(Arc::new(RwLock::new(user_preferences)));
+        data.insert::<GuildConfigs>(Arc::new(RwLock::new(guild_configs)));
+        data.insert::<Pronunciations>(Arc::new(RwLock::new(pronunciations)));
+        data.insert::<CommandPermissions>(Arc::new(RwLock::new(command_permissions)));
+        data.insert::<LastSpeakers>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<RateLimiter>(Arc::new(Mutex::new(RateLimiter::default())));
+        data.insert::<VoiceActivity>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<TtsProviders>(providers);
+        data.insert::<VoiceCatalog>(catalog);
+        data.insert::<AudioCache>(Arc::new(audio_cache));
+        data.insert::<PlaybackQueues>(Arc::new(Mutex::new(HashMap::default())));
     }
 
     let _ = client.start().await.map_err(|why| println!("Client ended: {:?}", why));
@@ -204,23 +364,15 @@ async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 
     let manager_lock = ctx.data.read().await
         .get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
-    let mut manager = manager_lock.lock().await;
-
-    if let Some(handler) = manager.get_mut(guild_id) {
-        let source = match voice::ytdl(&url).await {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
+    let in_voice = manager_lock.lock().await.get(guild_id).is_some();
 
-                check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg").await);
+    if in_voice {
+        playback::enqueue(ctx, guild_id, Track {
+            title: url.to_string(),
+            input: TrackInput::Url(url),
+        }).await;
 
-                return Ok(());
-            },
-        };
-
-        handler.play(source);
-
-        check_msg(msg.channel_id.say(&ctx.http, "Playing song").await);
+        check_msg(msg.channel_id.say(&ctx.http, "Queued song").await);
     } else {
         check_msg(msg.channel_id.say(&ctx.http, "Not in a voice channel to play in").await);
     }
@@ -228,7 +380,22 @@ async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     Ok(())
 }
 
-async fn handle_tts_message(ctx: &Context, msg: &Message) -> CommandResult {
+/// Voice channels have a text chat of their own, with read_voice_chat on the
+/// one the bot is connected to counts as linked to itself.
+async fn voice_chat_link(ctx: &Context, guild_id: GuildId, msg: &Message) -> Option<ChannelLink> {
+    if !guild_config(ctx, guild_id).await.read_voice_chat {
+        return None;
+    }
+    if presence::current_channel(ctx, guild_id).await != Some(msg.channel_id) {
+        return None;
+    }
+    Some(ChannelLink {
+        channel_id: msg.channel_id.0,
+        voice_channel_id: Some(msg.channel_id.0),
+    })
+}
+
+async fn handle_tts_message(ctx: &Context, msg: &Message, link: &ChannelLink) -> CommandResult {
     let guild_id = match ctx.cache.guild_channel(msg.channel_id).await {
         Some(channel) => {
             channel.guild_id
@@ -239,44 +406,236 @@ async fn handle_tts_message(ctx: &Context, msg: &Message) -> CommandResult {
             return Ok(());
         },
     };
-    let data_read = ctx.data.read().await;
-    let user_preferences_lock = data_read.get::<UserPreferences>().expect("Unable to read channel ID").clone();
-    let user_preferences = user_preferences_lock.read().await;
-    if let Some(prefs) = user_preferences.get(&msg.author.id.0) {
-        println!("Final voice: {:?}", prefs.voice);
-        let final_voice = Voice {
-            language_code: prefs.voice.language_code.to_string(),
-            name: prefs.voice.name.to_string(),
-            ssml_gender: prefs.voice.ssml_gender.to_string(),
-        };
-        
-        let manager_lock = data_read.get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
-        let mut manager = manager_lock.lock().await;
-        if let Some(handler) = manager.get_mut(guild_id) {
-            let cleaned_msg = clean_message(&msg);
-            let res = message_to_speech(&cleaned_msg, final_voice).await?;
-    
-            let mut file = File::create("voice.ogg")?;
-            file.write_all(&res)?;
-    
-            let source = match voice::ffmpeg("./voice.ogg").await {
-                Ok(source) => source,
-                Err(why) => {
-                    println!("Err starting source: {:?}", why);
-    
-                    check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg").await);
-    
-                    return Ok(());
-                },
-            };
-            handler.play(source);
+    let rate_limiter_lock = ctx.data.read().await
+        .get::<RateLimiter>().cloned().expect("Expected RateLimiter in TypeMap.");
+    let prefs = match speaker_pref(ctx, guild_id, &msg.author).await {
+        Some(prefs) => prefs,
+        None => return Ok(()),
+    };
+
+    let config = guild_config(ctx, guild_id).await;
+    if config.voice_present_only && !presence::is_listening(ctx, guild_id, msg.author.id).await {
+        return Ok(());
+    }
+    let voice_channel_id = link.voice_channel_id.map(ChannelId);
+    if !presence::can_read(ctx, guild_id, voice_channel_id).await {
+        return Ok(());
+    }
+    let options = NormalizeOptions {
+        // SSML turns *word* into emphasis, so the markers have to survive
+        markdown: !prefs.ssml,
+        ..NormalizeOptions::default()
+    };
+    let cleaned_msg = clean_message(ctx, msg, guild_id, &options).await;
+    if cleaned_msg.is_empty() {
+        return Ok(());
+    }
+
+    let admission = rate_limiter_lock.lock().await
+        .admit(guild_id.0, msg.author.id.0, cleaned_msg, &config.rate_limits(), Instant::now());
+    // Only messages that will be read may pull the bot into voice
+    match admission {
+        Admission::Allowed(text) => {
+            if !presence::auto_join(ctx, guild_id, msg.author.id, voice_channel_id).await {
+                return Ok(());
+            }
+            speak(ctx, msg, guild_id, text).await
+        },
+        Admission::Dropped { notify } => {
+            if notify && config.rate_limit_notify {
+                check_msg(msg.channel_id.say(&ctx.http, &format!("Slow down {}, I'm skipping messages for a bit", msg.author.mention())).await);
+            }
+            Ok(())
+        },
+        Admission::Held { first } => {
+            if first {
+                if config.rate_limit_notify {
+                    check_msg(msg.channel_id.say(&ctx.http, &format!("Slow down {}, I'll read your next messages together in a moment", msg.author.mention())).await);
+                }
+                tokio::spawn(flush_held_messages(ctx.clone(), msg.clone(), guild_id, voice_channel_id));
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Waits until the rate limits allow the user to speak again, then reads
+/// everything held back for them in one go.
+async fn flush_held_messages(ctx: Context, msg: Message, guild_id: GuildId, voice_channel_id: Option<ChannelId>) {
+    let rate_limiter_lock = ctx.data.read().await
+        .get::<RateLimiter>().cloned().expect("Expected RateLimiter in TypeMap.");
+    loop {
+        let limits = guild_config(&ctx, guild_id).await.rate_limits();
+        let flushed = rate_limiter_lock.lock().await
+            .flush(guild_id.0, msg.author.id.0, &limits, Instant::now());
+        match flushed {
+            Ok(Some(text)) => {
+                if !presence::auto_join(&ctx, guild_id, msg.author.id, voice_channel_id).await {
+                    return;
+                }
+                if let Err(why) = speak(&ctx, &msg, guild_id, text).await {
+                    println!("Error reading held messages: {:?}", why);
+                }
+                return;
+            },
+            Ok(None) => return,
+            Err(wait) => delay_for(wait).await,
+        }
+    }
+}
+
+/// Synthesizes normalized text in the author's voice and queues it, applying
+/// the guild's length limit and speaker announcements.
+async fn speak(ctx: &Context, msg: &Message, guild_id: GuildId, text: String) -> CommandResult {
+    // Held messages are read later, by which time the user may be gone
+    let prefs = match speaker_pref(ctx, guild_id, &msg.author).await {
+        Some(prefs) => prefs,
+        None => return Ok(()),
+    };
+    println!("Final voice: {:?}", prefs.voice);
+
+    let config = guild_config(ctx, guild_id).await;
+    // The announcement counts towards the length limit like the rest
+    let text = if should_announce(ctx, msg, &config).await {
+        // Guild messages carry the member, so this needs no cache or HTTP lookup
+        let nick = msg.member.as_ref()
+            .and_then(|member| member.nick.clone())
+            .unwrap_or_else(|| msg.author.name.to_string());
+        let name = normalize_for_guild(ctx, guild_id, &nick, &MentionNames::default(), &NormalizeOptions::default()).await;
+        if name.is_empty() {
+            text
+        } else {
+            format!("{} says {}", name, text)
         }
     } else {
+        text
+    };
+    read_out(ctx, msg, guild_id, &prefs, &text, &format!("Message from {}", msg.author.name)).await
+}
+
+/// Synthesizes normalized text within the guild's length limit and queues
+/// it up, as several tracks if the guild splits long messages.
+async fn read_out(ctx: &Context, msg: &Message, guild_id: GuildId, prefs: &UserPref, text: &str, title: &str) -> CommandResult {
+    let (providers, audio_cache) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<TtsProviders>().cloned().expect("Expected TtsProviders in TypeMap."),
+            data_read.get::<AudioCache>().cloned().expect("Expected AudioCache in TypeMap."),
+        )
+    };
+    let provider = match providers.for_voice(&prefs.voice) {
+        Ok(provider) => provider,
+        Err(why) => {
+            report_tts_error(ctx, msg, why).await;
+            return Ok(());
+        },
+    };
+
+    let config = guild_config(ctx, guild_id).await;
+    let max_length = config.max_message_length.max(1) as usize;
+    let utterances = limit_length(text, max_length, config.overflow_policy());
+    if utterances.is_empty() {
+        check_msg(msg.channel_id.say(&ctx.http, &format!("That's over {} characters, I'm not reading it", max_length)).await);
         return Ok(());
     }
+
+    let parts = utterances.len();
+    for (index, utterance) in utterances.into_iter().enumerate() {
+        let ssml = if prefs.ssml && provider.capabilities().ssml {
+            to_ssml(&utterance)
+        } else {
+            None
+        };
+        let request = match ssml {
+            Some(markup) => SpeechRequest {
+                text: markup,
+                voice: prefs.voice.clone(),
+                ssml: true,
+                audio: prefs.audio,
+            },
+            None => SpeechRequest {
+                text: utterance,
+                voice: prefs.voice.clone(),
+                ssml: false,
+                audio: prefs.audio,
+            },
+        };
+        let res = match audio_cache.synthesize(provider.as_ref(), &request).await {
+            Ok(res) => res,
+            Err(why) => {
+                report_tts_error(ctx, msg, why).await;
+                return Ok(());
+            },
+        };
+        let clip = SpeechClip::write(&res, provider.capabilities().audio_extension)?;
+
+        let title = if parts > 1 {
+            format!("{} ({}/{})", title, index + 1, parts)
+        } else {
+            title.to_string()
+        };
+        playback::enqueue(ctx, guild_id, Track {
+            title,
+            input: TrackInput::Clip(clip),
+        }).await;
+    }
     Ok(())
 }
 
+async fn should_announce(ctx: &Context, msg: &Message, config: &GuildConfig) -> bool {
+    let last_speakers_lock = ctx.data.read().await
+        .get::<LastSpeakers>().cloned().expect("Expected LastSpeakers in TypeMap.");
+    let mut last_speakers = last_speakers_lock.lock().await;
+    let now = Instant::now();
+    let window = Duration::from_secs(config.announce_window_secs.max(0) as u64);
+    let repeated = match last_speakers.insert(config.guild_id as u64, (msg.author.id, now)) {
+        Some((user_id, spoke_at)) => user_id == msg.author.id && now.duration_since(spoke_at) <= window,
+        None => false,
+    };
+    config.announce_speaker && !repeated
+}
+
+/// Every guild can pick its own prefix, DMs use the default one.
+#[hook]
+async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    Some(command_prefix(ctx, msg.guild_id).await)
+}
+
+/// Tells people why a command didn't run, e.g. when they lack permissions.
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
+    match error {
+        DispatchError::CheckFailed(_, Reason::User(reason)) => {
+            check_msg(msg.channel_id.say(&ctx.http, reason).await);
+        },
+        why => println!("Err dispatching command: {:?}", why),
+    }
+}
+
+async fn report_tts_error(ctx: &Context, msg: &Message, why: TtsError) {
+    println!("Err synthesizing message: {}", why);
+    let prefix = command_prefix(ctx, msg.guild_id).await;
+    check_msg(msg.channel_id.say(&ctx.http, why.user_message(&prefix)).await);
+}
+
+/// Groups lines into chunks of at most `limit` characters, so long listings
+/// can be sent in pieces that fit in a Discord message.
+fn paginate(lines: &[String], limit: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for line in lines {
+        if !page.is_empty() && page.len() + line.len() > limit {
+            pages.push(page);
+            page = String::new();
+        }
+        page.push_str(line);
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
 fn check_msg(result: SerenityResult<Message>) {
     if let Err(why) = result {
         println!("Error sending message: {:?}", why);
@@ -0,0 +1,10 @@
+use crate::schema::channel_links;
+
+#[derive(Queryable, Insertable, AsChangeset, Debug)]
+#[table_name = "channel_links"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ChannelLinkRecord {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub voice_channel_id: Option<i64>,
+}
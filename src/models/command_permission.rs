@@ -0,0 +1,9 @@
+use crate::schema::command_permissions;
+
+#[derive(Queryable, Insertable, AsChangeset, Debug)]
+#[table_name = "command_permissions"]
+pub struct CommandPermissionRecord {
+    pub guild_id: i64,
+    pub command: String,
+    pub level: String,
+}
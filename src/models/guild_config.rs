@@ -0,0 +1,337 @@
+use crate::normalize::OverflowPolicy;
+use crate::rate_limit::{Limits, RateLimitAction};
+use crate::tts::models::Voice;
+use crate::schema::guild_configs;
+
+#[derive(Queryable, Insertable, AsChangeset, Clone, Debug)]
+#[table_name = "guild_configs"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct GuildConfig {
+    pub guild_id: i64,
+    pub tts_provider: Option<String>,
+    pub announce_speaker: bool,
+    pub announce_window_secs: i32,
+    pub max_message_length: i32,
+    pub overflow_policy: String,
+    pub user_rate_limit: i32,
+    pub guild_rate_limit: i32,
+    pub rate_limit_action: String,
+    pub rate_limit_notify: bool,
+    pub auto_join: bool,
+    pub idle_timeout_mins: i32,
+    pub voice_present_only: bool,
+    pub read_voice_chat: bool,
+    pub default_voice_provider: Option<String>,
+    pub default_voice_name: Option<String>,
+    pub default_voice_language: Option<String>,
+    pub default_voice_gender: Option<String>,
+    pub read_everyone: bool,
+    pub dj_role_id: Option<i64>,
+    pub prefix: Option<String>,
+    pub canned_replies: bool,
+}
+
+impl GuildConfig {
+    pub const DEFAULT_PREFIX: &'static str = "g/";
+    pub const MAX_PREFIX_LENGTH: usize = 5;
+    pub const DEFAULT_ANNOUNCE_WINDOW_SECS: i32 = 60;
+    pub const ANNOUNCE_WINDOW_RANGE: (i32, i32) = (0, 3600);
+    pub const DEFAULT_MAX_MESSAGE_LENGTH: i32 = 500;
+    pub const MESSAGE_LENGTH_RANGE: (i32, i32) = (20, 5000);
+    pub const DEFAULT_USER_RATE_LIMIT: i32 = 6;
+    pub const DEFAULT_GUILD_RATE_LIMIT: i32 = 20;
+    pub const RATE_LIMIT_RANGE: (i32, i32) = (0, 600);
+    pub const DEFAULT_IDLE_TIMEOUT_MINS: i32 = 10;
+    pub const IDLE_TIMEOUT_RANGE: (i32, i32) = (0, 1440);
+
+    pub fn new(guild_id: u64) -> Self {
+        GuildConfig {
+            guild_id: guild_id as i64,
+            tts_provider: None,
+            announce_speaker: false,
+            announce_window_secs: Self::DEFAULT_ANNOUNCE_WINDOW_SECS,
+            max_message_length: Self::DEFAULT_MAX_MESSAGE_LENGTH,
+            overflow_policy: OverflowPolicy::Truncate.as_str().to_string(),
+            user_rate_limit: Self::DEFAULT_USER_RATE_LIMIT,
+            guild_rate_limit: Self::DEFAULT_GUILD_RATE_LIMIT,
+            rate_limit_action: RateLimitAction::Drop.as_str().to_string(),
+            rate_limit_notify: true,
+            auto_join: false,
+            idle_timeout_mins: Self::DEFAULT_IDLE_TIMEOUT_MINS,
+            voice_present_only: false,
+            read_voice_chat: false,
+            default_voice_provider: None,
+            default_voice_name: None,
+            default_voice_language: None,
+            default_voice_gender: None,
+            read_everyone: false,
+            dj_role_id: None,
+            prefix: None,
+            canned_replies: true,
+        }
+    }
+
+    /// The settings `g/config` knows about.
+    pub const KEYS: [&'static str; 18] = [
+        "prefix",
+        "canned_replies",
+        "provider",
+        "default_voice",
+        "read_everyone",
+        "dj_role",
+        "announce_speaker",
+        "announce_window_secs",
+        "max_message_length",
+        "overflow_policy",
+        "user_rate_limit",
+        "guild_rate_limit",
+        "rate_limit_action",
+        "rate_limit_notify",
+        "auto_join",
+        "idle_timeout_mins",
+        "voice_present_only",
+        "read_voice_chat",
+    ];
+
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(Self::DEFAULT_PREFIX)
+    }
+
+    /// The setting's current value, formatted for `g/config get`.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" }.to_string();
+        let value = match key {
+            "prefix" => self.prefix().to_string(),
+            "canned_replies" => on_off(self.canned_replies),
+            "provider" => self.tts_provider.clone().unwrap_or_else(|| "default".to_string()),
+            "default_voice" => self.default_voice_name.clone().unwrap_or_else(|| "none".to_string()),
+            "read_everyone" => on_off(self.read_everyone),
+            "dj_role" => match self.dj_role_id {
+                Some(role_id) => format!("<@&{}>", role_id),
+                None => "none".to_string(),
+            },
+            "announce_speaker" => on_off(self.announce_speaker),
+            "announce_window_secs" => self.announce_window_secs.to_string(),
+            "max_message_length" => self.max_message_length.to_string(),
+            "overflow_policy" => self.overflow_policy().as_str().to_string(),
+            "user_rate_limit" => self.user_rate_limit.to_string(),
+            "guild_rate_limit" => self.guild_rate_limit.to_string(),
+            "rate_limit_action" => self.rate_limits().action.as_str().to_string(),
+            "rate_limit_notify" => on_off(self.rate_limit_notify),
+            "auto_join" => on_off(self.auto_join),
+            "idle_timeout_mins" => self.idle_timeout_mins.to_string(),
+            "voice_present_only" => on_off(self.voice_present_only),
+            "read_voice_chat" => on_off(self.read_voice_chat),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Validates and applies a value for one of the plain settings. The
+    /// provider, default_voice and dj_role have to be looked up first, so
+    /// `g/config` sets those itself.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "prefix" => {
+                let length = value.chars().count();
+                if length == 0 || length > Self::MAX_PREFIX_LENGTH || value.contains(char::is_whitespace) {
+                    return Err(format!("The prefix needs 1 to {} characters without spaces", Self::MAX_PREFIX_LENGTH));
+                }
+                // Otherwise every message starting with that word is a command,
+                // the default's slash keeps it from matching ordinary words
+                if value.starts_with(char::is_alphanumeric) && value != Self::DEFAULT_PREFIX {
+                    return Err("The prefix can't start with a letter or digit".to_string());
+                }
+                self.prefix = Some(value.to_string());
+            },
+            "canned_replies" => self.canned_replies = parse_toggle(value)?,
+            "read_everyone" => self.read_everyone = parse_toggle(value)?,
+            "announce_speaker" => self.announce_speaker = parse_toggle(value)?,
+            "announce_window_secs" => self.announce_window_secs = parse_in_range(value, Self::ANNOUNCE_WINDOW_RANGE)?,
+            "max_message_length" => self.max_message_length = parse_in_range(value, Self::MESSAGE_LENGTH_RANGE)?,
+            "overflow_policy" => {
+                let policy = value.parse::<OverflowPolicy>()
+                    .map_err(|_| "Pick truncate, skip or split".to_string())?;
+                self.overflow_policy = policy.as_str().to_string();
+            },
+            "user_rate_limit" => self.user_rate_limit = parse_in_range(value, Self::RATE_LIMIT_RANGE)?,
+            "guild_rate_limit" => self.guild_rate_limit = parse_in_range(value, Self::RATE_LIMIT_RANGE)?,
+            "rate_limit_action" => {
+                let action = value.parse::<RateLimitAction>()
+                    .map_err(|_| "Pick drop or coalesce".to_string())?;
+                self.rate_limit_action = action.as_str().to_string();
+            },
+            "rate_limit_notify" => self.rate_limit_notify = parse_toggle(value)?,
+            "auto_join" => self.auto_join = parse_toggle(value)?,
+            "idle_timeout_mins" => self.idle_timeout_mins = parse_in_range(value, Self::IDLE_TIMEOUT_RANGE)?,
+            "voice_present_only" => self.voice_present_only = parse_toggle(value)?,
+            "read_voice_chat" => self.read_voice_chat = parse_toggle(value)?,
+            _ => return Err(format!("I don't have a {} setting", key)),
+        }
+        Ok(())
+    }
+
+    /// Puts a setting back to what new guilds start with.
+    pub fn reset(&mut self, key: &str) -> Result<(), String> {
+        let defaults = GuildConfig::new(self.guild_id as u64);
+        match key {
+            "prefix" => self.prefix = defaults.prefix,
+            "canned_replies" => self.canned_replies = defaults.canned_replies,
+            "provider" => self.tts_provider = defaults.tts_provider,
+            "default_voice" => self.set_default_voice(None),
+            "read_everyone" => self.read_everyone = defaults.read_everyone,
+            "dj_role" => self.dj_role_id = defaults.dj_role_id,
+            "announce_speaker" => self.announce_speaker = defaults.announce_speaker,
+            "announce_window_secs" => self.announce_window_secs = defaults.announce_window_secs,
+            "max_message_length" => self.max_message_length = defaults.max_message_length,
+            "overflow_policy" => self.overflow_policy = defaults.overflow_policy,
+            "user_rate_limit" => self.user_rate_limit = defaults.user_rate_limit,
+            "guild_rate_limit" => self.guild_rate_limit = defaults.guild_rate_limit,
+            "rate_limit_action" => self.rate_limit_action = defaults.rate_limit_action,
+            "rate_limit_notify" => self.rate_limit_notify = defaults.rate_limit_notify,
+            "auto_join" => self.auto_join = defaults.auto_join,
+            "idle_timeout_mins" => self.idle_timeout_mins = defaults.idle_timeout_mins,
+            "voice_present_only" => self.voice_present_only = defaults.voice_present_only,
+            "read_voice_chat" => self.read_voice_chat = defaults.read_voice_chat,
+            _ => return Err(format!("I don't have a {} setting", key)),
+        }
+        Ok(())
+    }
+
+    /// Unknown values in the database fall back to truncating.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy.parse().unwrap_or(OverflowPolicy::Truncate)
+    }
+
+    pub fn rate_limits(&self) -> Limits {
+        Limits {
+            user_per_minute: self.user_rate_limit.max(0) as u32,
+            guild_per_minute: self.guild_rate_limit.max(0) as u32,
+            action: self.rate_limit_action.parse().unwrap_or(RateLimitAction::Drop),
+            max_held_chars: self.max_message_length.max(1) as usize,
+        }
+    }
+
+    /// The voice unregistered users are read with, if the guild picked one.
+    pub fn default_voice(&self) -> Option<Voice> {
+        Some(Voice {
+            language_code: self.default_voice_language.clone()?,
+            name: self.default_voice_name.clone()?,
+            ssml_gender: self.default_voice_gender.clone()?,
+            provider: self.default_voice_provider.clone()?,
+        })
+    }
+
+    pub fn set_default_voice(&mut self, voice: Option<Voice>) {
+        self.default_voice_language = voice.as_ref().map(|voice| voice.language_code.to_string());
+        self.default_voice_name = voice.as_ref().map(|voice| voice.name.to_string());
+        self.default_voice_gender = voice.as_ref().map(|voice| voice.ssml_gender.to_string());
+        self.default_voice_provider = voice.map(|voice| voice.provider);
+    }
+}
+
+fn parse_toggle(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err("Use on or off".to_string()),
+    }
+}
+
+fn parse_in_range(value: &str, (min, max): (i32, i32)) -> Result<i32, String> {
+    match value.parse::<i32>() {
+        Ok(number) if number >= min && number <= max => Ok(number),
+        _ => Err(format!("That needs a number between {} and {}", min, max)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_can_be_read_and_reset() {
+        let mut config = GuildConfig::new(1);
+        for key in GuildConfig::KEYS.iter() {
+            assert!(config.get(key).is_some(), "{} can't be read", key);
+            assert_eq!(config.reset(key), Ok(()), "{} can't be reset", key);
+        }
+        assert_eq!(config.get("volume"), None);
+        assert!(config.set("volume", "11").is_err());
+        assert!(config.reset("volume").is_err());
+    }
+
+    #[test]
+    fn numbers_must_be_in_range() {
+        let mut config = GuildConfig::new(1);
+        let (min, max) = GuildConfig::MESSAGE_LENGTH_RANGE;
+        assert_eq!(config.set("max_message_length", &min.to_string()), Ok(()));
+        assert_eq!(config.max_message_length, min);
+        assert_eq!(config.set("max_message_length", &max.to_string()), Ok(()));
+        assert_eq!(config.max_message_length, max);
+        assert!(config.set("max_message_length", &(min - 1).to_string()).is_err());
+        assert!(config.set("max_message_length", &(max + 1).to_string()).is_err());
+        assert!(config.set("max_message_length", "lots").is_err());
+        assert_eq!(config.max_message_length, max);
+
+        let (min, max) = GuildConfig::IDLE_TIMEOUT_RANGE;
+        assert_eq!(config.set("idle_timeout_mins", &min.to_string()), Ok(()));
+        assert!(config.set("idle_timeout_mins", "-1").is_err());
+        assert!(config.set("idle_timeout_mins", &(max + 1).to_string()).is_err());
+        assert!(config.set("user_rate_limit", "2.5").is_err());
+    }
+
+    #[test]
+    fn toggles_take_on_or_off() {
+        let mut config = GuildConfig::new(1);
+        assert_eq!(config.set("auto_join", "ON"), Ok(()));
+        assert!(config.auto_join);
+        assert_eq!(config.set("auto_join", "no"), Ok(()));
+        assert!(!config.auto_join);
+        assert!(config.set("auto_join", "maybe").is_err());
+        assert!(config.set("auto_join", "1").is_err());
+        assert!(!config.auto_join);
+    }
+
+    #[test]
+    fn choices_must_be_known() {
+        let mut config = GuildConfig::new(1);
+        assert_eq!(config.set("overflow_policy", "Split"), Ok(()));
+        assert_eq!(config.overflow_policy(), OverflowPolicy::Split);
+        assert!(config.set("overflow_policy", "shorten").is_err());
+        assert!(config.set("rate_limit_action", "ignore").is_err());
+        assert_eq!(config.get("rate_limit_action"), Some("drop".to_string()));
+    }
+
+    #[test]
+    fn prefixes_are_short_symbols() {
+        let mut config = GuildConfig::new(1);
+        assert_eq!(config.prefix(), GuildConfig::DEFAULT_PREFIX);
+        assert_eq!(config.set("prefix", "!"), Ok(()));
+        assert_eq!(config.prefix(), "!");
+        assert_eq!(config.set("prefix", "$tts/"), Ok(()));
+        assert!(config.set("prefix", "a").is_err());
+        assert!(config.set("prefix", "tts").is_err());
+        assert!(config.set("prefix", "9/").is_err());
+        assert!(config.set("prefix", "é!").is_err());
+        assert!(config.set("prefix", "! !").is_err());
+        assert!(config.set("prefix", "!!!!!!").is_err());
+        assert!(config.set("prefix", "").is_err());
+        assert_eq!(config.prefix(), "$tts/");
+        assert_eq!(config.set("prefix", GuildConfig::DEFAULT_PREFIX), Ok(()));
+        assert_eq!(config.set("prefix", "!"), Ok(()));
+        assert_eq!(config.reset("prefix"), Ok(()));
+        assert_eq!(config.prefix(), GuildConfig::DEFAULT_PREFIX);
+    }
+
+    #[test]
+    fn reset_restores_defaults() {
+        let mut config = GuildConfig::new(1);
+        config.set("announce_window_secs", "0").unwrap();
+        config.set("canned_replies", "off").unwrap();
+        config.reset("announce_window_secs").unwrap();
+        config.reset("canned_replies").unwrap();
+        assert_eq!(config.announce_window_secs, GuildConfig::new(1).announce_window_secs);
+        assert!(config.canned_replies);
+    }
+}
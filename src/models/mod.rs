@@ -1 +1,5 @@
-
+pub mod channel_link;
+pub mod command_permission;
+pub mod guild_config;
+pub mod pronunciation;
+pub mod user_preference;
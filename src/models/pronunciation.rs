@@ -0,0 +1,9 @@
+use crate::schema::pronunciations;
+
+#[derive(Queryable, Insertable, AsChangeset, Debug)]
+#[table_name = "pronunciations"]
+pub struct PronunciationRecord {
+    pub guild_id: i64,
+    pub word: String,
+    pub replacement: String,
+}
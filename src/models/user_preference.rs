@@ -0,0 +1,15 @@
+use crate::schema::user_preferences;
+
+#[derive(Queryable, Insertable, AsChangeset, Debug)]
+#[table_name = "user_preferences"]
+pub struct UserPreferenceRecord {
+    pub user_id: i64,
+    pub language_code: String,
+    pub voice_name: String,
+    pub ssml_gender: String,
+    pub provider: String,
+    pub ssml: bool,
+    pub speaking_rate: f64,
+    pub pitch: f64,
+    pub volume_gain_db: f64,
+}
@@ -0,0 +1,598 @@
+use chrono::NaiveDateTime;
+use regex::{Captures, Regex};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+lazy_static! {
+    static ref FENCED_CODE: Regex = Regex::new(r"(?s)```.*?```").unwrap();
+    static ref INLINE_CODE: Regex = Regex::new(r"`([^`]+)`").unwrap();
+    static ref SPOILER: Regex = Regex::new(r"(?s)\|\|.+?\|\|").unwrap();
+    static ref URL: Regex = Regex::new(r"\w+://(?:[^\s/?#<>@]+@)?([^\s/?#<>:]+)(?::\d+)?[^\s<>]*").unwrap();
+    static ref USER_MENTION: Regex = Regex::new(r"<@!?([0-9]+)>").unwrap();
+    static ref ROLE_MENTION: Regex = Regex::new(r"<@&([0-9]+)>").unwrap();
+    static ref CHANNEL_MENTION: Regex = Regex::new(r"<#([0-9]+)>").unwrap();
+    static ref CUSTOM_EMOJI: Regex = Regex::new(r"<a?:(\w+):[0-9]+>").unwrap();
+    static ref TIMESTAMP: Regex = Regex::new(r"<t:(-?[0-9]+)(?::([tTdDfFR]))?>").unwrap();
+    // Only paired markers, so "2*3" and "5 * 4" keep their operator
+    static ref MARKERS: Regex = Regex::new(r"\*\*(\S(?:.*?\S)?)\*\*|__(\S(?:.*?\S)?)__|~~(.+?)~~|\*(\S(?:.*?\S)?)\*").unwrap();
+    static ref UNDERSCORES: Regex = Regex::new(r"(^|\W)_(\S(?:.*?\S)?)_(\W|$)").unwrap();
+    static ref LINE_PREFIXES: Regex = Regex::new(r"(?m)^(?:>>> |> |#{1,3} )").unwrap();
+    static ref SENTENCE_END: Regex = Regex::new(r"[.!?]+\s+").unwrap();
+}
+
+/// Names to speak for the ids mentioned in a message.
+#[derive(Default, Debug)]
+pub struct MentionNames {
+    pub users: HashMap<u64, String>,
+    pub roles: HashMap<u64, String>,
+    pub channels: HashMap<u64, String>,
+}
+
+/// A guild's replacement for a word TTS gets wrong, matched as a whole word
+/// and ignoring case.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pronunciation {
+    pub word: String,
+    pub replacement: String,
+}
+
+/// Which rules `normalize` applies, all of them are on by default.
+#[derive(Clone, Copy, Debug)]
+pub struct NormalizeOptions {
+    pub pronunciations: bool,
+    pub code_blocks: bool,
+    pub spoilers: bool,
+    pub urls: bool,
+    pub mentions: bool,
+    pub custom_emoji: bool,
+    pub timestamps: bool,
+    pub markdown: bool,
+    pub unicode_emoji: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        NormalizeOptions {
+            pronunciations: true,
+            code_blocks: true,
+            spoilers: true,
+            urls: true,
+            mentions: true,
+            custom_emoji: true,
+            timestamps: true,
+            markdown: true,
+            unicode_emoji: true,
+        }
+    }
+}
+
+/// Turns a raw Discord message into something a TTS engine can read out.
+pub fn normalize(
+    text: &str,
+    names: &MentionNames,
+    dictionary: &[Pronunciation],
+    options: &NormalizeOptions,
+) -> String {
+    let mut text = text.to_string();
+    // Code goes first, so nothing inside a fenced block gets picked up by the
+    // other rules. Inline code is kept and normalized like the rest.
+    if options.code_blocks {
+        text = code_blocks(&text);
+    }
+    if options.spoilers {
+        text = spoilers(&text);
+    }
+    if options.urls {
+        text = urls(&text);
+    }
+    if options.mentions {
+        text = mentions(&text, names);
+    }
+    if options.custom_emoji {
+        text = custom_emoji(&text);
+    }
+    if options.timestamps {
+        text = timestamps(&text);
+    }
+    if options.markdown {
+        text = markdown(&text);
+    }
+    if options.unicode_emoji {
+        text = unicode_emoji(&text);
+    }
+    // Last, so nicknames and emoji names can be respelled too.
+    if options.pronunciations {
+        text = pronunciations(&text, dictionary);
+    }
+    collapse_whitespace(&text)
+}
+
+/// Fenced blocks are announced rather than read, inline code is read as is.
+fn code_blocks(text: &str) -> String {
+    let text = FENCED_CODE.replace_all(text, " code block ");
+    INLINE_CODE.replace_all(&text, "$1").to_string()
+}
+
+fn spoilers(text: &str) -> String {
+    SPOILER.replace_all(text, " spoiler ").to_string()
+}
+
+/// Replaces links with "link to" and their domain.
+fn urls(text: &str) -> String {
+    URL.replace_all(text, |caps: &Captures| {
+        let host = caps[1].trim_start_matches("www.");
+        format!("link to {}", host)
+    }).to_string()
+}
+
+fn mentions(text: &str, names: &MentionNames) -> String {
+    let text = USER_MENTION.replace_all(text, |caps: &Captures| {
+        let id: u64 = caps[1].parse().unwrap_or(0);
+        names.users.get(&id).cloned().unwrap_or_else(|| "someone".to_string())
+    });
+
+    let text = ROLE_MENTION.replace_all(&text, |caps: &Captures| {
+        let id: u64 = caps[1].parse().unwrap_or(0);
+        match names.roles.get(&id) {
+            Some(name) => format!("@{}", name),
+            None => "@role".to_string(),
+        }
+    });
+
+    CHANNEL_MENTION.replace_all(&text, |caps: &Captures| {
+        let id: u64 = caps[1].parse().unwrap_or(0);
+        match names.channels.get(&id) {
+            Some(name) => format!("hash {}", name),
+            None => String::new(),
+        }
+    }).to_string()
+}
+
+/// Ids of the channels mentioned as `<#id>`, so their names can be looked up.
+pub fn channel_mention_ids(text: &str) -> Vec<u64> {
+    CHANNEL_MENTION.captures_iter(text)
+        .filter_map(|caps| caps[1].parse().ok())
+        .collect()
+}
+
+/// `<:blob_wave:1234>` is read as "blob wave".
+fn custom_emoji(text: &str) -> String {
+    CUSTOM_EMOJI.replace_all(text, |caps: &Captures| {
+        format!(" {} ", caps[1].replace('_', " "))
+    }).to_string()
+}
+
+/// `<t:1602000000:f>` is read as a date and/or time in UTC, depending on
+/// the style letter.
+fn timestamps(text: &str) -> String {
+    TIMESTAMP.replace_all(text, |caps: &Captures| {
+        let seconds: i64 = caps[1].parse().unwrap_or(0);
+        let date_time = match NaiveDateTime::from_timestamp_opt(seconds, 0) {
+            Some(date_time) => date_time,
+            None => return "a timestamp".to_string(),
+        };
+        let format = match caps.get(2).map(|style| style.as_str()) {
+            Some("t") | Some("T") => "%H:%M UTC",
+            Some("d") | Some("D") => "%B %-d, %Y",
+            _ => "%B %-d, %Y at %H:%M UTC",
+        };
+        date_time.format(format).to_string()
+    }).to_string()
+}
+
+/// Drops the markers for bold, italics, underline, strikethrough, headings
+/// and quotes but keeps the text they wrap.
+fn markdown(text: &str) -> String {
+    let mut text = text.to_string();
+    // Nested markers like ***both*** come off one layer at a time
+    loop {
+        let stripped = MARKERS.replace_all(&text, |caps: &Captures| {
+            caps.iter().skip(1).flatten().map(|m| m.as_str()).collect::<String>()
+        }).to_string();
+        if stripped == text {
+            break;
+        }
+        text = stripped;
+    }
+    let text = UNDERSCORES.replace_all(&text, "$1$2$3");
+    LINE_PREFIXES.replace_all(&text, "").to_string()
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF
+        | 0x2600..=0x27BF
+        | 0x2B00..=0x2BFF
+        | 0x2190..=0x21FF
+        | 0x200D
+        | 0xFE0F
+    )
+}
+
+fn emoji_name(c: char) -> Option<&'static str> {
+    let name = match c {
+        '😂' => "laughing",
+        '🤣' => "rolling on the floor laughing",
+        '😀' | '😃' | '😄' | '😁' => "grinning",
+        '😅' => "sweat smile",
+        '😊' | '🙂' => "smiling",
+        '😉' => "winking",
+        '😍' => "heart eyes",
+        '😎' => "cool",
+        '😏' => "smirking",
+        '🤔' => "thinking",
+        '🙄' => "eye roll",
+        '😳' => "flushed",
+        '😢' => "sad",
+        '😭' => "crying",
+        '😡' | '😠' => "angry",
+        '😱' => "screaming",
+        '🥺' => "pleading",
+        '😴' => "sleeping",
+        '🤡' => "clown",
+        '💀' => "skull",
+        '💩' => "poop",
+        '❤' | '💖' | '💕' => "heart",
+        '👍' => "thumbs up",
+        '👎' => "thumbs down",
+        '👋' => "waving",
+        '👌' => "okay",
+        '👀' => "eyes",
+        '🙏' => "please",
+        '🤷' => "shrug",
+        '🔥' => "fire",
+        '🎉' => "party",
+        '✨' => "sparkles",
+        '💯' => "hundred",
+        '✅' => "check",
+        '❌' => "cross",
+        '🐐' => "goat",
+        '🍕' => "pizza",
+        '🍺' => "beer",
+        '☕' => "coffee",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Reads common emoji by name and drops the rest. A run of the same emoji
+/// is only read once.
+fn unicode_emoji(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_name = None;
+    for c in text.chars() {
+        if !is_emoji(c) {
+            if !c.is_whitespace() {
+                last_name = None;
+            }
+            result.push(c);
+            continue;
+        }
+        let name = match emoji_name(c) {
+            Some(name) => name,
+            None => continue,
+        };
+        if last_name != Some(name) {
+            result.push(' ');
+            result.push_str(name);
+            result.push(' ');
+        }
+        last_name = Some(name);
+    }
+    result
+}
+
+/// Applies the dictionary in a single pass, so a replacement is never
+/// replaced again. Longer words win over shorter ones they contain.
+fn pronunciations(text: &str, dictionary: &[Pronunciation]) -> String {
+    if dictionary.is_empty() {
+        return text.to_string();
+    }
+    let mut entries: Vec<&Pronunciation> = dictionary.iter().collect();
+    entries.sort_by_key(|entry| Reverse(entry.word.len()));
+
+    // \b only works next to word characters, so words like "c++" go without.
+    let word_boundary = |c: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    };
+    let alternatives: Vec<String> = entries.iter()
+        .map(|entry| format!(
+            "{}{}{}",
+            word_boundary(entry.word.chars().next()),
+            regex::escape(&entry.word),
+            word_boundary(entry.word.chars().last()),
+        ))
+        .collect();
+    let re = match Regex::new(&format!("(?i){}", alternatives.join("|"))) {
+        Ok(re) => re,
+        Err(_) => return text.to_string(),
+    };
+    re.replace_all(text, |caps: &Captures| {
+        let matched = caps[0].to_lowercase();
+        entries.iter()
+            .find(|entry| entry.word.to_lowercase() == matched)
+            .map(|entry| entry.replacement.to_string())
+            .unwrap_or_else(|| caps[0].to_string())
+    }).to_string()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// What to do with messages longer than the guild allows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    /// Read up to the limit, then say "and more".
+    Truncate,
+    /// Don't read the message at all.
+    Skip,
+    /// Read all of it as several utterances, broken up between sentences.
+    Split,
+}
+
+impl OverflowPolicy {
+    pub const ALL: [OverflowPolicy; 3] = [OverflowPolicy::Truncate, OverflowPolicy::Skip, OverflowPolicy::Split];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Truncate => "truncate",
+            OverflowPolicy::Skip => "skip",
+            OverflowPolicy::Split => "split",
+        }
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OverflowPolicy::ALL.iter()
+            .find(|policy| policy.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(())
+    }
+}
+
+const TRUNCATED_SUFFIX: &str = " and more";
+
+/// Fits normalized text into utterances of at most `max_chars` characters.
+/// Skipping yields nothing, truncating one utterance ending in "and more".
+///
+/// This counts characters, while Google caps its input at 5000 bytes, so
+/// a long enough message in a non-Latin script can still be refused there.
+pub fn limit_length(text: &str, max_chars: usize, policy: OverflowPolicy) -> Vec<String> {
+    if text.chars().count() <= max_chars {
+        return vec![text.to_string()];
+    }
+    match policy {
+        OverflowPolicy::Skip => Vec::new(),
+        OverflowPolicy::Truncate => {
+            // The suffix is read too, so it has to fit within the limit
+            let budget = max_chars.saturating_sub(TRUNCATED_SUFFIX.chars().count());
+            vec![format!("{}{}", truncate_at_word(text, budget), TRUNCATED_SUFFIX)]
+        },
+        OverflowPolicy::Split => split_sentences(text, max_chars),
+    }
+}
+
+/// The longest prefix of `text` within `max_chars` that doesn't end
+/// mid-word, unless the first word alone is already too long.
+fn truncate_at_word(text: &str, max_chars: usize) -> &str {
+    let end = match text.char_indices().nth(max_chars) {
+        Some((end, _)) => end,
+        None => return text,
+    };
+    let head = &text[..end];
+    if text[end..].starts_with(char::is_whitespace) {
+        return head.trim_end();
+    }
+    match head.rfind(char::is_whitespace) {
+        Some(space) if space > 0 => head[..space].trim_end(),
+        _ => head,
+    }
+}
+
+fn split_sentences(text: &str, max_chars: usize) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for end in SENTENCE_END.find_iter(text) {
+        sentences.push(text[start..end.end()].trim());
+        start = end.end();
+    }
+    sentences.push(text[start..].trim());
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    for sentence in sentences.into_iter().filter(|sentence| !sentence.is_empty()) {
+        if !chunk.is_empty() && chunk.chars().count() + 1 + sentence.chars().count() <= max_chars {
+            chunk.push(' ');
+            chunk.push_str(sentence);
+            continue;
+        }
+        if !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        // Sentences that don't fit on their own are broken up between words
+        let mut rest = sentence;
+        while rest.chars().count() > max_chars {
+            let head = truncate_at_word(rest, max_chars);
+            chunks.push(head.to_string());
+            rest = rest[head.len()..].trim_start();
+        }
+        chunk.push_str(rest);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize_default(text: &str) -> String {
+        normalize(text, &MentionNames::default(), &[], &NormalizeOptions::default())
+    }
+
+    #[test]
+    fn code_blocks_are_announced() {
+        assert_eq!(normalize_default("look ```fn main() {}``` here"), "look code block here");
+        assert_eq!(normalize_default("run `cargo build` now"), "run cargo build now");
+    }
+
+    #[test]
+    fn spoilers_are_hidden() {
+        assert_eq!(normalize_default("he dies ||in the end|| lol"), "he dies spoiler lol");
+    }
+
+    #[test]
+    fn urls_become_their_domain() {
+        assert_eq!(normalize_default("see https://www.example.com/a?b=c ok"), "see link to example.com ok");
+        assert_eq!(normalize_default("http://user@host.io:8080/x"), "link to host.io");
+    }
+
+    #[test]
+    fn user_mentions_use_names() {
+        let mut names = MentionNames::default();
+        names.users.insert(1, "Alice".to_string());
+        let options = NormalizeOptions::default();
+        assert_eq!(normalize("hi <@1> and <@!1>", &names, &[], &options), "hi Alice and Alice");
+        assert_eq!(normalize("hi <@2>", &names, &[], &options), "hi someone");
+    }
+
+    #[test]
+    fn role_mentions_are_spoken() {
+        let mut names = MentionNames::default();
+        names.roles.insert(5, "mods".to_string());
+        let options = NormalizeOptions::default();
+        assert_eq!(normalize("ping <@&5>", &names, &[], &options), "ping @mods");
+        assert_eq!(normalize("ping <@&6>", &names, &[], &options), "ping @role");
+    }
+
+    #[test]
+    fn channel_mentions_use_names_or_disappear() {
+        let mut names = MentionNames::default();
+        names.channels.insert(9, "general".to_string());
+        let options = NormalizeOptions::default();
+        assert_eq!(normalize("go to <#9>", &names, &[], &options), "go to hash general");
+        assert_eq!(normalize("go to <#8> now", &names, &[], &options), "go to now");
+    }
+
+    #[test]
+    fn channel_mention_ids_are_found() {
+        assert_eq!(channel_mention_ids("<#1> and <#22>, not <@3>"), vec![1, 22]);
+    }
+
+    #[test]
+    fn custom_emoji_are_read_by_name() {
+        assert_eq!(normalize_default("hey<:blob_wave:123456>"), "hey blob wave");
+        assert_eq!(normalize_default("<a:party_parrot:42>!"), "party parrot !");
+    }
+
+    #[test]
+    fn timestamps_are_read_as_dates() {
+        assert_eq!(normalize_default("at <t:1602000000:f>"), "at October 6, 2020 at 16:00 UTC");
+        assert_eq!(normalize_default("on <t:1602000000:D>"), "on October 6, 2020");
+        assert_eq!(normalize_default("by <t:1602000000:t>"), "by 16:00 UTC");
+    }
+
+    #[test]
+    fn markdown_markers_are_dropped() {
+        assert_eq!(normalize_default("**bold** *it* __under__ ~~gone~~"), "bold it under gone");
+        assert_eq!(normalize_default("> quoted _text_ keeps snake_case"), "quoted text keeps snake_case");
+        assert_eq!(normalize_default("# Heading"), "Heading");
+        assert_eq!(normalize_default("***both*** and __*mixed*__"), "both and mixed");
+    }
+
+    #[test]
+    fn lone_asterisks_are_kept() {
+        assert_eq!(normalize_default("2*3 is 6"), "2*3 is 6");
+        assert_eq!(normalize_default("5 * 4 * 3 is 60"), "5 * 4 * 3 is 60");
+        assert_eq!(normalize_default("*fixed typo"), "*fixed typo");
+    }
+
+    #[test]
+    fn unicode_emoji_are_named_or_dropped() {
+        assert_eq!(normalize_default("nice 👍"), "nice thumbs up");
+        assert_eq!(normalize_default("lmao 😂😂😂"), "lmao laughing");
+        assert_eq!(normalize_default("ok 🫠 then"), "ok then");
+    }
+
+    fn entry(word: &str, replacement: &str) -> Pronunciation {
+        Pronunciation {
+            word: word.to_string(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn pronunciations_replace_whole_words_ignoring_case() {
+        let dictionary = vec![entry("gif", "jif"), entry("wow", "world of warcraft")];
+        let options = NormalizeOptions::default();
+        assert_eq!(
+            normalize("GIF or gifs, WoW", &MentionNames::default(), &dictionary, &options),
+            "jif or gifs, world of warcraft",
+        );
+    }
+
+    #[test]
+    fn pronunciations_prefer_longer_words_and_never_chain() {
+        let dictionary = vec![entry("c", "see"), entry("c++", "see plus plus"), entry("see", "look")];
+        let options = NormalizeOptions::default();
+        assert_eq!(
+            normalize("I write c++ and c", &MentionNames::default(), &dictionary, &options),
+            "I write see plus plus and see",
+        );
+    }
+
+    #[test]
+    fn short_messages_are_left_alone() {
+        for policy in OverflowPolicy::ALL.iter() {
+            assert_eq!(limit_length("hello there", 20, *policy), vec!["hello there"]);
+        }
+    }
+
+    #[test]
+    fn long_messages_are_truncated_between_words() {
+        assert_eq!(
+            limit_length("the quick brown fox jumps", 20, OverflowPolicy::Truncate),
+            vec!["the quick and more"],
+        );
+        for max_chars in 10..25 {
+            let truncated = limit_length("the quick brown fox jumps over", max_chars, OverflowPolicy::Truncate);
+            assert!(truncated[0].chars().count() <= max_chars, "{:?} is over {}", truncated, max_chars);
+        }
+        assert!(limit_length("the quick brown fox jumps", 12, OverflowPolicy::Skip).is_empty());
+    }
+
+    #[test]
+    fn long_messages_are_split_between_sentences() {
+        assert_eq!(
+            limit_length("One two. Three four! Five six seven eight nine ten?", 20, OverflowPolicy::Split),
+            vec!["One two. Three four!", "Five six seven eight", "nine ten?"],
+        );
+    }
+
+    #[test]
+    fn overflow_policies_parse_by_name() {
+        assert_eq!("Split".parse(), Ok(OverflowPolicy::Split));
+        assert_eq!("nope".parse::<OverflowPolicy>(), Err(()));
+    }
+
+    #[test]
+    fn rules_can_be_turned_off() {
+        let options = NormalizeOptions {
+            markdown: false,
+            urls: false,
+            ..NormalizeOptions::default()
+        };
+        assert_eq!(
+            normalize("*hey* https://example.com", &MentionNames::default(), &[], &options),
+            "*hey* https://example.com",
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::preferences::{CommandPermissions, guild_config};
+use crate::GENERAL_GROUP;
+use serenity::framework::standard::{
+    Args,
+    CheckResult,
+    CommandOptions,
+    macros::check,
+};
+use serenity::model::{
+    channel::Message,
+    guild::Role,
+    id::{GuildId, RoleId},
+    permissions::Permissions,
+};
+use serenity::prelude::*;
+use std::{ptr, str::FromStr};
+
+/// Who may run a command. Each level includes the ones before it.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum PermissionLevel {
+    Everyone,
+    /// Manage Server, or the guild's DJ role.
+    Dj,
+    /// Manage Server only.
+    Admin,
+}
+
+impl PermissionLevel {
+    pub const ALL: [PermissionLevel; 3] = [PermissionLevel::Everyone, PermissionLevel::Dj, PermissionLevel::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionLevel::Everyone => "everyone",
+            PermissionLevel::Dj => "dj",
+            PermissionLevel::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for PermissionLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PermissionLevel::ALL.iter()
+            .find(|level| level.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(())
+    }
+}
+
+/// Commands that manage permissions themselves, these can't be overridden
+/// so nobody locks the server admins out.
+pub const LOCKED_COMMANDS: [&str; 2] = ["permissions", "djrole"];
+
+/// Voice control and server settings, see `default_level`.
+const DJ_COMMANDS: [&str; 26] = [
+    "join", "leave", "deafen", "undeafen", "mute", "unmute",
+    "play", "skip", "clear", "scare",
+    "link", "unlink", "voicechat", "provider", "defaultvoice", "readeveryone",
+    "announce", "length", "ratelimit", "autojoin", "idle", "voiceonly",
+    "say-as", "say-as remove", "config set", "config reset",
+];
+
+/// The level a command needs unless the guild overrode it: voice control
+/// and server settings need the DJ level, everything personal is open.
+pub fn default_level(command: &str) -> PermissionLevel {
+    if LOCKED_COMMANDS.contains(&command) {
+        PermissionLevel::Admin
+    } else if DJ_COMMANDS.contains(&command) {
+        PermissionLevel::Dj
+    } else {
+        PermissionLevel::Everyone
+    }
+}
+
+/// The name permissions are stored under: a command's first name, and for
+/// subcommands their parent's name and theirs, e.g. "say-as remove".
+pub fn command_key(options: &CommandOptions) -> String {
+    for command in GENERAL_GROUP.options.commands.iter() {
+        if ptr::eq(command.options, options) {
+            return command.options.names[0].to_string();
+        }
+        for sub_command in command.options.sub_commands.iter() {
+            if ptr::eq(sub_command.options, options) {
+                return format!("{} {}", command.options.names[0], sub_command.options.names[0]);
+            }
+        }
+    }
+    options.names.first().copied().unwrap_or_default().to_string()
+}
+
+/// Every command and subcommand permissions can be set for.
+pub fn command_keys() -> Vec<String> {
+    let mut keys = Vec::new();
+    for command in GENERAL_GROUP.options.commands.iter() {
+        keys.push(command_key(command.options));
+        for sub_command in command.options.sub_commands.iter() {
+            keys.push(command_key(sub_command.options));
+        }
+    }
+    keys
+}
+
+pub async fn required_level(ctx: &Context, guild_id: GuildId, command: &str) -> PermissionLevel {
+    let overrides_lock = ctx.data.read().await
+        .get::<CommandPermissions>().cloned().expect("Expected CommandPermissions in TypeMap.");
+    let overrides = overrides_lock.read().await;
+    match overrides.get(&guild_id.0).and_then(|commands| commands.get(command)) {
+        Some(level) => *level,
+        None => default_level(command),
+    }
+}
+
+/// The highest level the message's author has in the guild. The member
+/// usually isn't cached on large guilds, but the message carries its roles.
+pub async fn member_level(ctx: &Context, guild_id: GuildId, msg: &Message) -> PermissionLevel {
+    let roles = match &msg.member {
+        Some(member) => member.roles.clone(),
+        None => match guild_id.member(ctx, msg.author.id).await {
+            Ok(member) => member.roles,
+            Err(why) => {
+                println!("Err fetching member {} of {}: {:?}", msg.author.id, guild_id, why);
+                return PermissionLevel::Everyone;
+            },
+        },
+    };
+    let dj_role_id = guild_config(ctx, guild_id).await.dj_role_id.map(|id| RoleId(id as u64));
+    let level = ctx.cache.guild_field(guild_id, |guild| {
+        // The @everyone role shares the guild's id
+        let permissions = roles.iter()
+            .chain(std::iter::once(&RoleId(guild_id.0)))
+            .filter_map(|role_id| guild.roles.get(role_id))
+            .fold(Permissions::empty(), |permissions, role| permissions | role.permissions);
+        if guild.owner_id == msg.author.id || permissions.manage_guild() || permissions.administrator() {
+            PermissionLevel::Admin
+        } else if dj_role_id.map(|role_id| roles.contains(&role_id)) == Some(true) {
+            PermissionLevel::Dj
+        } else {
+            PermissionLevel::Everyone
+        }
+    }).await;
+    level.unwrap_or(PermissionLevel::Everyone)
+}
+
+/// Finds one of the guild's roles by mention, id or name, ignoring case.
+pub async fn find_role(ctx: &Context, guild_id: GuildId, text: &str) -> Option<Role> {
+    let text = text.trim();
+    if let Ok(role_id) = text.parse::<RoleId>() {
+        if let Some(role) = ctx.cache.role(guild_id, role_id).await {
+            return Some(role);
+        }
+    }
+    let name = text.to_lowercase();
+    ctx.cache.guild_field(guild_id, |guild| {
+        guild.roles.values().find(|role| role.name.to_lowercase() == name).cloned()
+    }).await.flatten()
+}
+
+// Runs before every command in the group, see `default_level`.
+#[check]
+#[name = "Permitted"]
+async fn permitted_check(ctx: &Context, msg: &Message, _: &mut Args, options: &CommandOptions) -> CheckResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return CheckResult::Success,
+    };
+    let command = command_key(options);
+    let required = required_level(ctx, guild_id, &command).await;
+    if member_level(ctx, guild_id, msg).await >= required {
+        return CheckResult::Success;
+    }
+
+    let config = guild_config(ctx, guild_id).await;
+    let reason = match required {
+        PermissionLevel::Admin => format!("Only people with Manage Server can use {}{}", config.prefix(), command),
+        _ => {
+            let dj_role = match config.dj_role_id {
+                Some(role_id) => ctx.cache.role(guild_id, role_id as u64).await.map(|role| role.name),
+                None => None,
+            };
+            match dj_role {
+                Some(name) => format!("You need Manage Server or the {} role to use {}{}", name, config.prefix(), command),
+                None => format!("You need Manage Server to use {}{}", config.prefix(), command),
+            }
+        },
+    };
+    CheckResult::new_user(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_parse_ignoring_case() {
+        assert_eq!("everyone".parse(), Ok(PermissionLevel::Everyone));
+        assert_eq!("DJ".parse(), Ok(PermissionLevel::Dj));
+        assert_eq!("Admin".parse(), Ok(PermissionLevel::Admin));
+        assert_eq!("default".parse::<PermissionLevel>(), Err(()));
+        assert_eq!("".parse::<PermissionLevel>(), Err(()));
+    }
+
+    #[test]
+    fn higher_levels_include_lower_ones() {
+        assert!(PermissionLevel::Admin >= PermissionLevel::Dj);
+        assert!(PermissionLevel::Dj >= PermissionLevel::Everyone);
+        assert!(PermissionLevel::Admin >= PermissionLevel::Everyone);
+        assert!(PermissionLevel::Everyone < PermissionLevel::Dj);
+        assert!(PermissionLevel::Dj < PermissionLevel::Admin);
+    }
+
+    #[test]
+    fn gated_commands_exist() {
+        let keys = command_keys();
+        for command in LOCKED_COMMANDS.iter().chain(DJ_COMMANDS.iter()) {
+            assert!(keys.contains(&command.to_string()), "g/{} isn't a command", command);
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_open() {
+        assert_eq!(default_level("permissions"), PermissionLevel::Admin);
+        assert_eq!(default_level("say-as remove"), PermissionLevel::Dj);
+        assert_eq!(default_level("say-as list"), PermissionLevel::Everyone);
+        assert_eq!(default_level("register"), PermissionLevel::Everyone);
+    }
+}
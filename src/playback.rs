@@ -0,0 +1,197 @@
+use crate::presence::{self, VoiceActivity};
+use crate::tts::clip::SpeechClip;
+use crate::VoiceManager;
+use serenity::client::bridge::voice::ClientVoiceManager;
+use serenity::model::id::GuildId;
+use serenity::prelude::*;
+use serenity::voice::{self, AudioSource, LockedAudio};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::delay_for;
+
+pub enum TrackInput {
+    /// Synthesized speech, the file is removed once the track is done.
+    Clip(SpeechClip),
+    /// A file that ships with the bot, like the jump scare.
+    File(String),
+    /// Anything youtube-dl can stream.
+    Url(String),
+}
+
+pub struct Track {
+    pub title: String,
+    pub input: TrackInput,
+}
+
+#[derive(Default)]
+pub struct GuildQueue {
+    tracks: VecDeque<Track>,
+    now_playing: Option<String>,
+    skip_requested: bool,
+    running: bool,
+}
+
+pub struct PlaybackQueues;
+
+impl TypeMapKey for PlaybackQueues {
+    type Value = Arc<Mutex<HashMap<u64, GuildQueue>>>;
+}
+
+async fn queues_and_manager(ctx: &Context) -> (Arc<Mutex<HashMap<u64, GuildQueue>>>, Arc<Mutex<ClientVoiceManager>>) {
+    let data_read = ctx.data.read().await;
+    (
+        data_read.get::<PlaybackQueues>().cloned().expect("Expected PlaybackQueues in TypeMap."),
+        data_read.get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap."),
+    )
+}
+
+/// Appends a track to the guild's queue, starting playback if nothing
+/// is playing yet.
+pub async fn enqueue(ctx: &Context, guild_id: GuildId, track: Track) {
+    let (queues_lock, manager_lock) = queues_and_manager(ctx).await;
+    let activity_lock = ctx.data.read().await
+        .get::<VoiceActivity>().cloned().expect("Expected VoiceActivity in TypeMap.");
+    presence::record_activity(&activity_lock, guild_id).await;
+    let mut queues = queues_lock.lock().await;
+    let queue = queues.entry(guild_id.0).or_default();
+    queue.tracks.push_back(track);
+    if !queue.running {
+        queue.running = true;
+        tokio::spawn(run_queue(queues_lock.clone(), manager_lock, activity_lock, guild_id));
+    }
+}
+
+/// Stops the current track, the next one in line starts right after.
+/// Returns false when nothing was playing.
+pub async fn skip(ctx: &Context, guild_id: GuildId) -> bool {
+    let (queues_lock, _) = queues_and_manager(ctx).await;
+    let mut queues = queues_lock.lock().await;
+    match queues.get_mut(&guild_id.0) {
+        Some(queue) if queue.now_playing.is_some() => {
+            queue.skip_requested = true;
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Drops every track that hasn't started yet, returning how many there were.
+pub async fn clear(ctx: &Context, guild_id: GuildId) -> usize {
+    let (queues_lock, _) = queues_and_manager(ctx).await;
+    let mut queues = queues_lock.lock().await;
+    match queues.get_mut(&guild_id.0) {
+        Some(queue) => {
+            let count = queue.tracks.len();
+            queue.tracks.clear();
+            count
+        },
+        None => 0,
+    }
+}
+
+/// Whether the guild's queue is playing something right now.
+pub async fn is_active(ctx: &Context, guild_id: GuildId) -> bool {
+    let (queues_lock, _) = queues_and_manager(ctx).await;
+    let queues = queues_lock.lock().await;
+    match queues.get(&guild_id.0) {
+        Some(queue) => queue.running,
+        None => false,
+    }
+}
+
+/// The title of the current track and the titles of everything after it.
+pub async fn list(ctx: &Context, guild_id: GuildId) -> (Option<String>, Vec<String>) {
+    let (queues_lock, _) = queues_and_manager(ctx).await;
+    let queues = queues_lock.lock().await;
+    match queues.get(&guild_id.0) {
+        Some(queue) => (
+            queue.now_playing.clone(),
+            queue.tracks.iter().map(|track| track.title.to_string()).collect(),
+        ),
+        None => (None, Vec::new()),
+    }
+}
+
+async fn open_source(input: &TrackInput) -> serenity::Result<Box<dyn AudioSource>> {
+    match input {
+        TrackInput::Clip(clip) => voice::ffmpeg(clip.path()).await,
+        TrackInput::File(path) => voice::ffmpeg(path).await,
+        TrackInput::Url(url) => voice::ytdl(url).await,
+    }
+}
+
+/// Plays the guild's queue front to back, one track at a time, and exits
+/// once it runs dry or the bot is no longer in a voice channel.
+async fn run_queue(
+    queues_lock: Arc<Mutex<HashMap<u64, GuildQueue>>>,
+    manager_lock: Arc<Mutex<ClientVoiceManager>>,
+    activity_lock: Arc<Mutex<HashMap<u64, Instant>>>,
+    guild_id: GuildId,
+) {
+    loop {
+        let track = {
+            let mut queues = queues_lock.lock().await;
+            let queue = queues.entry(guild_id.0).or_default();
+            match queue.tracks.pop_front() {
+                Some(track) => {
+                    queue.now_playing = Some(track.title.to_string());
+                    queue.skip_requested = false;
+                    track
+                },
+                None => {
+                    queue.now_playing = None;
+                    queue.running = false;
+                    return;
+                },
+            }
+        };
+
+        let source = match open_source(&track.input).await {
+            Ok(source) => source,
+            Err(why) => {
+                println!("Err starting source for {}: {:?}", track.title, why);
+                continue;
+            },
+        };
+
+        let audio: LockedAudio = {
+            let mut manager = manager_lock.lock().await;
+            match manager.get_mut(guild_id) {
+                Some(handler) => handler.play_returning(source),
+                None => {
+                    let mut queues = queues_lock.lock().await;
+                    let queue = queues.entry(guild_id.0).or_default();
+                    queue.tracks.clear();
+                    queue.now_playing = None;
+                    queue.running = false;
+                    return;
+                },
+            }
+        };
+
+        loop {
+            delay_for(Duration::from_millis(100)).await;
+            if audio.lock().await.finished {
+                break;
+            }
+            let skip_requested = {
+                let queues = queues_lock.lock().await;
+                matches!(queues.get(&guild_id.0), Some(queue) if queue.skip_requested)
+            };
+            let mut manager = manager_lock.lock().await;
+            // Leaving the channel drops the audio without ever marking it finished.
+            match manager.get_mut(guild_id) {
+                Some(handler) if skip_requested => {
+                    handler.stop();
+                    break;
+                },
+                Some(_) => {},
+                None => break,
+            }
+        }
+        presence::record_activity(&activity_lock, guild_id).await;
+    }
+}
@@ -0,0 +1,373 @@
+use crate::db::{self, Database, DbPool, DbResult};
+use crate::models::{
+    channel_link::ChannelLinkRecord,
+    command_permission::CommandPermissionRecord,
+    guild_config::GuildConfig,
+    pronunciation::PronunciationRecord,
+    user_preference::UserPreferenceRecord,
+};
+use crate::normalize::Pronunciation;
+use crate::permissions::PermissionLevel;
+use crate::schema::{channel_links, command_permissions, guild_configs, pronunciations, user_preferences};
+use crate::tts::{
+    models::Voice,
+    provider::{AudioSettings, TtsProvider, TtsProviders},
+};
+use diesel::prelude::*;
+use serenity::model::{
+    id::{ChannelId, GuildId, UserId},
+    user::User,
+};
+use serenity::prelude::{Context, TypeMapKey};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
+
+pub struct ChannelRegistry;
+pub struct CommandPermissions;
+pub struct GuildConfigs;
+pub struct Pronunciations;
+pub struct UserPreferences;
+
+/// The guild's linked text channels, see `ChannelLink`.
+impl TypeMapKey for ChannelRegistry {
+    type Value = Arc<RwLock<HashMap<u64, Vec<ChannelLink>>>>;
+}
+
+/// Per guild, the commands whose permission level was changed from the
+/// default, see `permissions::default_level`.
+impl TypeMapKey for CommandPermissions {
+    type Value = Arc<RwLock<HashMap<u64, HashMap<String, PermissionLevel>>>>;
+}
+
+impl TypeMapKey for GuildConfigs {
+    type Value = Arc<RwLock<HashMap<u64, GuildConfig>>>;
+}
+
+impl TypeMapKey for Pronunciations {
+    type Value = Arc<RwLock<HashMap<u64, Vec<Pronunciation>>>>;
+}
+
+/// A text channel whose messages are read out, optionally only into one
+/// specific voice channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelLink {
+    pub channel_id: u64,
+    pub voice_channel_id: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct UserPref {
+    pub voice: Voice,
+    /// Whether messages are sent as SSML, see `tts::ssml`.
+    pub ssml: bool,
+    pub audio: AudioSettings,
+}
+
+impl TypeMapKey for UserPreferences {
+    type Value = Arc<RwLock<HashMap<u64, UserPref>>>;
+}
+
+impl UserPref {
+    pub fn new(voice: Voice) -> Self {
+        UserPref {
+            voice,
+            ssml: false,
+            audio: AudioSettings::default(),
+        }
+    }
+
+    fn from_record(record: UserPreferenceRecord) -> Self {
+        UserPref {
+            voice: Voice {
+                language_code: record.language_code,
+                name: record.voice_name,
+                ssml_gender: record.ssml_gender,
+                provider: record.provider,
+            },
+            ssml: record.ssml,
+            audio: AudioSettings {
+                speaking_rate: record.speaking_rate,
+                pitch: record.pitch,
+                volume_gain_db: record.volume_gain_db,
+            },
+        }
+    }
+
+    fn to_record(&self, user_id: u64) -> UserPreferenceRecord {
+        UserPreferenceRecord {
+            user_id: user_id as i64,
+            language_code: self.voice.language_code.to_string(),
+            voice_name: self.voice.name.to_string(),
+            ssml_gender: self.voice.ssml_gender.to_string(),
+            provider: self.voice.provider.to_string(),
+            ssml: self.ssml,
+            speaking_rate: self.audio.speaking_rate,
+            pitch: self.audio.pitch,
+            volume_gain_db: self.audio.volume_gain_db,
+        }
+    }
+}
+
+pub fn load_user_preferences(pool: &DbPool) -> DbResult<HashMap<u64, UserPref>> {
+    let conn = pool.get()?;
+    let records = user_preferences::table.load::<UserPreferenceRecord>(&conn)?;
+    Ok(records.into_iter()
+        .map(|record| (record.user_id as u64, UserPref::from_record(record)))
+        .collect())
+}
+
+pub fn save_user_preference(pool: &DbPool, user_id: u64, pref: &UserPref) -> DbResult<()> {
+    let conn = pool.get()?;
+    let record = pref.to_record(user_id);
+    diesel::insert_into(user_preferences::table)
+        .values(&record)
+        .on_conflict(user_preferences::user_id)
+        .do_update()
+        .set(&record)
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn delete_user_preference(pool: &DbPool, user_id: u64) -> DbResult<()> {
+    let conn = pool.get()?;
+    diesel::delete(user_preferences::table.find(user_id as i64))
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn load_channel_links(pool: &DbPool) -> DbResult<HashMap<u64, Vec<ChannelLink>>> {
+    let conn = pool.get()?;
+    let records = channel_links::table
+        .order(channel_links::channel_id)
+        .load::<ChannelLinkRecord>(&conn)?;
+    let mut links: HashMap<u64, Vec<ChannelLink>> = HashMap::new();
+    for record in records {
+        links.entry(record.guild_id as u64).or_default().push(ChannelLink {
+            channel_id: record.channel_id as u64,
+            voice_channel_id: record.voice_channel_id.map(|id| id as u64),
+        });
+    }
+    Ok(links)
+}
+
+pub fn save_channel_link(pool: &DbPool, guild_id: u64, link: &ChannelLink) -> DbResult<()> {
+    let conn = pool.get()?;
+    let record = ChannelLinkRecord {
+        guild_id: guild_id as i64,
+        channel_id: link.channel_id as i64,
+        voice_channel_id: link.voice_channel_id.map(|id| id as i64),
+    };
+    diesel::insert_into(channel_links::table)
+        .values(&record)
+        .on_conflict((channel_links::guild_id, channel_links::channel_id))
+        .do_update()
+        .set(&record)
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn delete_channel_link(pool: &DbPool, guild_id: u64, channel_id: u64) -> DbResult<()> {
+    let conn = pool.get()?;
+    diesel::delete(channel_links::table.find((guild_id as i64, channel_id as i64)))
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn load_command_permissions(pool: &DbPool) -> DbResult<HashMap<u64, HashMap<String, PermissionLevel>>> {
+    let conn = pool.get()?;
+    let records = command_permissions::table.load::<CommandPermissionRecord>(&conn)?;
+    let mut overrides: HashMap<u64, HashMap<String, PermissionLevel>> = HashMap::new();
+    for record in records {
+        match record.level.parse() {
+            Ok(level) => {
+                overrides.entry(record.guild_id as u64).or_default().insert(record.command, level);
+            },
+            Err(_) => println!("Skipping unknown permission level {} for {}", record.level, record.command),
+        }
+    }
+    Ok(overrides)
+}
+
+pub fn save_command_permission(pool: &DbPool, guild_id: u64, command: &str, level: PermissionLevel) -> DbResult<()> {
+    let conn = pool.get()?;
+    let record = CommandPermissionRecord {
+        guild_id: guild_id as i64,
+        command: command.to_string(),
+        level: level.as_str().to_string(),
+    };
+    diesel::insert_into(command_permissions::table)
+        .values(&record)
+        .on_conflict((command_permissions::guild_id, command_permissions::command))
+        .do_update()
+        .set(&record)
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn delete_command_permission(pool: &DbPool, guild_id: u64, command: &str) -> DbResult<()> {
+    let conn = pool.get()?;
+    diesel::delete(command_permissions::table.find((guild_id as i64, command)))
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn load_guild_configs(pool: &DbPool) -> DbResult<HashMap<u64, GuildConfig>> {
+    let conn = pool.get()?;
+    let configs = guild_configs::table.load::<GuildConfig>(&conn)?;
+    Ok(configs.into_iter()
+        .map(|config| (config.guild_id as u64, config))
+        .collect())
+}
+
+pub fn save_guild_config(pool: &DbPool, config: &GuildConfig) -> DbResult<()> {
+    let conn = pool.get()?;
+    diesel::insert_into(guild_configs::table)
+        .values(config)
+        .on_conflict(guild_configs::guild_id)
+        .do_update()
+        .set(config)
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn load_pronunciations(pool: &DbPool) -> DbResult<HashMap<u64, Vec<Pronunciation>>> {
+    let conn = pool.get()?;
+    let records = pronunciations::table
+        .order(pronunciations::word)
+        .load::<PronunciationRecord>(&conn)?;
+    let mut dictionaries: HashMap<u64, Vec<Pronunciation>> = HashMap::new();
+    for record in records {
+        dictionaries.entry(record.guild_id as u64).or_default().push(Pronunciation {
+            word: record.word,
+            replacement: record.replacement,
+        });
+    }
+    Ok(dictionaries)
+}
+
+pub fn save_pronunciation(pool: &DbPool, guild_id: u64, pronunciation: &Pronunciation) -> DbResult<()> {
+    let conn = pool.get()?;
+    let record = PronunciationRecord {
+        guild_id: guild_id as i64,
+        word: pronunciation.word.to_string(),
+        replacement: pronunciation.replacement.to_string(),
+    };
+    diesel::insert_into(pronunciations::table)
+        .values(&record)
+        .on_conflict((pronunciations::guild_id, pronunciations::word))
+        .do_update()
+        .set(&record)
+        .execute(&conn)?;
+    Ok(())
+}
+
+pub fn delete_pronunciation(pool: &DbPool, guild_id: u64, word: &str) -> DbResult<()> {
+    let conn = pool.get()?;
+    diesel::delete(pronunciations::table.find((guild_id as i64, word)))
+        .execute(&conn)?;
+    Ok(())
+}
+
+/// The link for a text channel, if its messages should be read out.
+pub async fn channel_link(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelLink> {
+    let channel_map_lock = ctx.data.read().await
+        .get::<ChannelRegistry>().cloned().expect("Expected ChannelRegistry in TypeMap.");
+    let channel_map = channel_map_lock.read().await;
+    channel_map.get(&guild_id.0)?
+        .iter()
+        .find(|link| link.channel_id == channel_id.0)
+        .copied()
+}
+
+/// The settings to read a user's messages with: their own once registered,
+/// otherwise the guild's default voice if it reads everyone. Other bots are
+/// only read if they were registered.
+pub async fn speaker_pref(ctx: &Context, guild_id: GuildId, user: &User) -> Option<UserPref> {
+    let user_preferences_lock = ctx.data.read().await
+        .get::<UserPreferences>().cloned().expect("Expected UserPreferences in TypeMap.");
+    if let Some(pref) = user_preferences_lock.read().await.get(&user.id.0) {
+        return Some(pref.clone());
+    }
+    let config = guild_config(ctx, guild_id).await;
+    if !config.read_everyone || user.bot {
+        return None;
+    }
+    config.default_voice().map(UserPref::new)
+}
+
+/// The guild's configuration, or the defaults if it never changed anything.
+pub async fn guild_config(ctx: &Context, guild_id: GuildId) -> GuildConfig {
+    let configs_lock = ctx.data.read().await
+        .get::<GuildConfigs>().cloned().expect("Expected GuildConfigs in TypeMap.");
+    let configs = configs_lock.read().await;
+    configs.get(&guild_id.0).cloned().unwrap_or_else(|| GuildConfig::new(guild_id.0))
+}
+
+/// The prefix commands start with in this guild, the default one in DMs.
+pub async fn command_prefix(ctx: &Context, guild_id: Option<GuildId>) -> String {
+    match guild_id {
+        Some(guild_id) => guild_config(ctx, guild_id).await.prefix().to_string(),
+        None => GuildConfig::DEFAULT_PREFIX.to_string(),
+    }
+}
+
+/// The TTS provider whose voices are offered in this guild.
+pub async fn guild_provider(ctx: &Context, guild_id: Option<GuildId>) -> Arc<dyn TtsProvider> {
+    let providers = ctx.data.read().await
+        .get::<TtsProviders>().cloned().expect("Expected TtsProviders in TypeMap.");
+    match guild_id {
+        Some(guild_id) => providers.for_guild(&guild_config(ctx, guild_id).await),
+        None => providers.default_provider(),
+    }
+}
+
+/// Applies `change` to the guild's configuration once it's written through
+/// to the database, returning the updated configuration.
+pub async fn update_guild_config<F>(ctx: &Context, guild_id: GuildId, change: F) -> DbResult<GuildConfig>
+    where F: FnOnce(&mut GuildConfig)
+{
+    let (configs_lock, pool) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<GuildConfigs>().cloned().expect("Expected GuildConfigs in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    // No lock is held during the save, every message reads these configs
+    let mut config = configs_lock.read().await.get(&guild_id.0).cloned()
+        .unwrap_or_else(|| GuildConfig::new(guild_id.0));
+    change(&mut config);
+    let saved = config.clone();
+    if let Err(why) = db::run(&pool, move |pool| save_guild_config(pool, &saved)).await {
+        println!("Error saving guild config: {:?}", why);
+        return Err(why);
+    }
+    configs_lock.write().await.insert(guild_id.0, config.clone());
+    Ok(config)
+}
+
+/// Applies `change` to a registered user's preferences once it's written
+/// through to the database. Returns `None` for users without a voice.
+pub async fn update_user_pref<F>(ctx: &Context, user_id: UserId, change: F) -> DbResult<Option<UserPref>>
+    where F: FnOnce(&mut UserPref)
+{
+    let (user_preferences_lock, pool) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<UserPreferences>().cloned().expect("Expected UserPreferences in TypeMap."),
+            data_read.get::<Database>().cloned().expect("Expected Database in TypeMap."),
+        )
+    };
+    let mut pref = match user_preferences_lock.read().await.get(&user_id.0) {
+        Some(pref) => pref.clone(),
+        None => return Ok(None),
+    };
+    change(&mut pref);
+    let saved = pref.clone();
+    if let Err(why) = db::run(&pool, move |pool| save_user_preference(pool, user_id.0, &saved)).await {
+        println!("Error saving voice preference: {:?}", why);
+        return Err(why);
+    }
+    user_preferences_lock.write().await.insert(user_id.0, pref.clone());
+    Ok(Some(pref))
+}
@@ -0,0 +1,195 @@
+use crate::playback;
+use crate::preferences::guild_config;
+use crate::VoiceManager;
+use serenity::model::{
+    id::{ChannelId, GuildId, UserId},
+    voice::VoiceState,
+};
+use serenity::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::delay_for;
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When each guild's voice connection last did something, for the guilds
+/// the bot is in voice in. Guilds in here have an idle watcher running.
+pub struct VoiceActivity;
+
+impl TypeMapKey for VoiceActivity {
+    type Value = Arc<Mutex<HashMap<u64, Instant>>>;
+}
+
+/// Notes that something was queued or played in the guild just now. Only
+/// guilds with an idle watcher are tracked.
+pub async fn record_activity(activity_lock: &Arc<Mutex<HashMap<u64, Instant>>>, guild_id: GuildId) {
+    if let Some(last_active) = activity_lock.lock().await.get_mut(&guild_id.0) {
+        *last_active = Instant::now();
+    }
+}
+
+pub async fn in_voice(ctx: &Context, guild_id: GuildId) -> bool {
+    let manager_lock = ctx.data.read().await
+        .get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
+    let in_voice = manager_lock.lock().await.get(guild_id).is_some();
+    in_voice
+}
+
+/// The voice channel the bot is connected to in the guild, if any.
+pub async fn current_channel(ctx: &Context, guild_id: GuildId) -> Option<ChannelId> {
+    let manager_lock = ctx.data.read().await
+        .get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
+    let channel_id = manager_lock.lock().await
+        .get(guild_id)
+        .and_then(|handler| handler.channel_id);
+    channel_id
+}
+
+/// Joins the voice channel and starts watching it for inactivity.
+/// Returns false when joining failed.
+pub async fn join(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> bool {
+    let (manager_lock, activity_lock) = {
+        let data_read = ctx.data.read().await;
+        (
+            data_read.get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap."),
+            data_read.get::<VoiceActivity>().cloned().expect("Expected VoiceActivity in TypeMap."),
+        )
+    };
+    if manager_lock.lock().await.join(guild_id, channel_id).is_none() {
+        return false;
+    }
+    let watching = activity_lock.lock().await
+        .insert(guild_id.0, Instant::now())
+        .is_some();
+    if !watching {
+        tokio::spawn(watch_idle(ctx.clone(), guild_id));
+    }
+    true
+}
+
+/// Leaves the guild's voice channel and drops whatever was still queued.
+/// Returns false when the bot wasn't in voice.
+pub async fn leave(ctx: &Context, guild_id: GuildId) -> bool {
+    let manager_lock = ctx.data.read().await
+        .get::<VoiceManager>().cloned().expect("Expected VoiceManager in TypeMap.");
+    {
+        let mut manager = manager_lock.lock().await;
+        if manager.get(guild_id).is_none() {
+            return false;
+        }
+        manager.remove(guild_id);
+    }
+    playback::clear(ctx, guild_id).await;
+    true
+}
+
+/// Whether a message could be read into voice, without joining anything
+/// yet: the bot is already in the right channel, or the guild lets it join.
+pub async fn can_read(ctx: &Context, guild_id: GuildId, linked_channel_id: Option<ChannelId>) -> bool {
+    if in_voice(ctx, guild_id).await {
+        return linked_channel_id.is_none() || current_channel(ctx, guild_id).await == linked_channel_id;
+    }
+    guild_config(ctx, guild_id).await.auto_join
+}
+
+/// Joins the voice channel a message should be read into if the bot isn't in
+/// voice yet and the guild allows it: the link's voice channel if it has
+/// one, otherwise the author's. Returns whether the bot is in the right
+/// voice channel afterwards.
+pub async fn auto_join(ctx: &Context, guild_id: GuildId, user_id: UserId, linked_channel_id: Option<ChannelId>) -> bool {
+    if !can_read(ctx, guild_id, linked_channel_id).await {
+        return false;
+    }
+    if in_voice(ctx, guild_id).await {
+        return true;
+    }
+    let channel_id = match linked_channel_id {
+        Some(channel_id) => Some(channel_id),
+        None => ctx.cache.guild_field(guild_id, |guild| {
+            guild.voice_states.get(&user_id).and_then(|voice_state| voice_state.channel_id)
+        }).await.flatten(),
+    };
+    match channel_id {
+        Some(channel_id) => join(ctx, guild_id, channel_id).await,
+        None => false,
+    }
+}
+
+/// Whether the user is in the voice channel the bot is in. Until the cache
+/// knows the bot's channel, being in any voice channel is enough, as that's
+/// where an auto join takes the bot.
+pub async fn is_listening(ctx: &Context, guild_id: GuildId, user_id: UserId) -> bool {
+    let current_user_id = ctx.cache.current_user_id().await;
+    let listening = ctx.cache.guild_field(guild_id, |guild| {
+        let channel_of = |user_id| guild.voice_states.get(&user_id).and_then(|voice_state| voice_state.channel_id);
+        match (channel_of(user_id), channel_of(current_user_id)) {
+            (Some(user_channel), Some(bot_channel)) => user_channel == bot_channel,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }).await;
+    listening == Some(true)
+}
+
+/// Leaves once nobody but bots are left in the bot's voice channel, and
+/// cleans up after someone else disconnected the bot.
+pub async fn voice_state_changed(ctx: &Context, guild_id: GuildId, new: &VoiceState) {
+    if !in_voice(ctx, guild_id).await {
+        return;
+    }
+    let current_user_id = ctx.cache.current_user_id().await;
+    if new.user_id == current_user_id && new.channel_id.is_none() {
+        leave(ctx, guild_id).await;
+        return;
+    }
+    let listeners = ctx.cache.guild_field(guild_id, |guild| {
+        let channel_id = guild.voice_states.get(&current_user_id)
+            .and_then(|voice_state| voice_state.channel_id)?;
+        let listeners = guild.voice_states.values()
+            .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+            .filter(|voice_state| match guild.members.get(&voice_state.user_id) {
+                Some(member) => !member.user.bot,
+                None => voice_state.user_id != current_user_id,
+            })
+            .count();
+        Some(listeners)
+    }).await.flatten();
+    // Right after joining the cache may not know where the bot is yet
+    if listeners == Some(0) {
+        println!("Leaving voice in {}, nobody is listening", guild_id);
+        leave(ctx, guild_id).await;
+    }
+}
+
+/// Leaves the guild's voice channel after nothing was queued or played for
+/// the guild's idle timeout, and stops once the bot is no longer in voice.
+async fn watch_idle(ctx: Context, guild_id: GuildId) {
+    let activity_lock = ctx.data.read().await
+        .get::<VoiceActivity>().cloned().expect("Expected VoiceActivity in TypeMap.");
+    loop {
+        delay_for(IDLE_CHECK_INTERVAL).await;
+        if !in_voice(&ctx, guild_id).await {
+            activity_lock.lock().await.remove(&guild_id.0);
+            return;
+        }
+        // Long tracks only count as activity when queued and when done
+        if playback::is_active(&ctx, guild_id).await {
+            continue;
+        }
+
+        let timeout_mins = guild_config(&ctx, guild_id).await.idle_timeout_mins;
+        let idle_for = match activity_lock.lock().await.get(&guild_id.0) {
+            Some(last_active) => last_active.elapsed(),
+            None => return,
+        };
+        if timeout_mins > 0 && idle_for >= Duration::from_secs(timeout_mins as u64 * 60) {
+            println!("Leaving voice in {} after {} idle minutes", guild_id, timeout_mins);
+            leave(&ctx, guild_id).await;
+            activity_lock.lock().await.remove(&guild_id.0);
+            return;
+        }
+    }
+}
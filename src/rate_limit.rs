@@ -0,0 +1,316 @@
+use serenity::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// What happens to messages sent while a user or guild is over its limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateLimitAction {
+    /// The message isn't read.
+    Drop,
+    /// The messages are held and read together once the limit allows it.
+    Coalesce,
+}
+
+impl RateLimitAction {
+    pub const ALL: [RateLimitAction; 2] = [RateLimitAction::Drop, RateLimitAction::Coalesce];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitAction::Drop => "drop",
+            RateLimitAction::Coalesce => "coalesce",
+        }
+    }
+}
+
+impl FromStr for RateLimitAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RateLimitAction::ALL.iter()
+            .find(|action| action.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(())
+    }
+}
+
+/// Messages per minute allowed for each user and for the guild as a whole,
+/// 0 means unlimited.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub user_per_minute: u32,
+    pub guild_per_minute: u32,
+    pub action: RateLimitAction,
+    /// How much text is held for a user when coalescing, later messages
+    /// are dropped once it's reached.
+    pub max_held_chars: usize,
+}
+
+/// A bucket refills completely within a minute, so one untouched for that
+/// long is as good as a new one and can be forgotten.
+const BUCKET_REFILL: Duration = Duration::from_secs(60);
+
+/// Holds up to a minute's worth of messages and refills continuously, so
+/// short bursts are fine but a steady stream gets throttled.
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(per_minute: u32, now: Instant) -> Self {
+        TokenBucket {
+            tokens: per_minute as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, per_minute: u32, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_minute as f64 / 60.0).min(per_minute as f64);
+        self.updated = now;
+    }
+
+    /// How long until a token is available, zero if one already is.
+    fn wait(&mut self, per_minute: u32, now: Instant) -> Duration {
+        if per_minute == 0 {
+            return Duration::from_secs(0);
+        }
+        self.refill(per_minute, now);
+        if self.tokens >= 1.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) * 60.0 / per_minute as f64)
+        }
+    }
+
+    fn take(&mut self, per_minute: u32) {
+        if per_minute > 0 {
+            self.tokens -= 1.0;
+        }
+    }
+}
+
+pub enum Admission {
+    /// Read this now, it may include messages held back earlier.
+    Allowed(String),
+    /// Over the limit and dropped. `notify` is only set for the first
+    /// message dropped in a row, so warnings don't become spam themselves.
+    Dropped { notify: bool },
+    /// Over the limit and held to be read later. `first` is set when
+    /// nothing was held for the user yet, so a flush needs scheduling.
+    Held { first: bool },
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    users: HashMap<(u64, u64), TokenBucket>,
+    guilds: HashMap<u64, TokenBucket>,
+    pending: HashMap<(u64, u64), String>,
+    notified: HashSet<(u64, u64)>,
+    pruned: Option<Instant>,
+}
+
+impl TypeMapKey for RateLimiter {
+    type Value = Arc<Mutex<RateLimiter>>;
+}
+
+impl RateLimiter {
+    /// Takes a token from both the user's and the guild's bucket if both
+    /// have one, otherwise returns how long to wait before trying again.
+    fn try_take(&mut self, guild_id: u64, user_id: u64, limits: &Limits, now: Instant) -> Result<(), Duration> {
+        let user = self.users.entry((guild_id, user_id))
+            .or_insert_with(|| TokenBucket::full(limits.user_per_minute, now));
+        let user_wait = user.wait(limits.user_per_minute, now);
+        let guild = self.guilds.entry(guild_id)
+            .or_insert_with(|| TokenBucket::full(limits.guild_per_minute, now));
+        let guild_wait = guild.wait(limits.guild_per_minute, now);
+        let wait = user_wait.max(guild_wait);
+        if wait > Duration::from_secs(0) {
+            return Err(wait);
+        }
+        user.take(limits.user_per_minute);
+        guild.take(limits.guild_per_minute);
+        Ok(())
+    }
+
+    /// Forgets the buckets that have refilled completely, at most once per
+    /// refill period.
+    fn prune(&mut self, now: Instant) {
+        if let Some(pruned) = self.pruned {
+            if now.duration_since(pruned) < BUCKET_REFILL {
+                return;
+            }
+        }
+        self.pruned = Some(now);
+        let is_full = |bucket: &TokenBucket| now.duration_since(bucket.updated) >= BUCKET_REFILL;
+        self.users.retain(|_, bucket| !is_full(bucket));
+        self.guilds.retain(|_, bucket| !is_full(bucket));
+        let users = &self.users;
+        self.notified.retain(|key| users.contains_key(key));
+    }
+
+    /// Decides what to do with a user's message given the guild's limits.
+    pub fn admit(&mut self, guild_id: u64, user_id: u64, text: String, limits: &Limits, now: Instant) -> Admission {
+        self.prune(now);
+        let key = (guild_id, user_id);
+        // Once something is held, later messages queue up behind it so
+        // they're read in order, until there's a message's worth of text.
+        if let (RateLimitAction::Coalesce, Some(pending)) = (limits.action, self.pending.get_mut(&key)) {
+            if pending.chars().count() + 1 + text.chars().count() > limits.max_held_chars {
+                return Admission::Dropped { notify: false };
+            }
+            pending.push(' ');
+            pending.push_str(&text);
+            return Admission::Held { first: false };
+        }
+        if self.try_take(guild_id, user_id, limits, now).is_ok() {
+            self.notified.remove(&key);
+            return Admission::Allowed(text);
+        }
+        match limits.action {
+            RateLimitAction::Drop => Admission::Dropped { notify: self.notified.insert(key) },
+            RateLimitAction::Coalesce => {
+                self.pending.insert(key, text);
+                Admission::Held { first: true }
+            },
+        }
+    }
+
+    /// Releases everything held for the user if the limits allow it now,
+    /// otherwise returns how long to wait. `Ok(None)` means nothing is held.
+    pub fn flush(&mut self, guild_id: u64, user_id: u64, limits: &Limits, now: Instant) -> Result<Option<String>, Duration> {
+        let key = (guild_id, user_id);
+        if !self.pending.contains_key(&key) {
+            return Ok(None);
+        }
+        self.try_take(guild_id, user_id, limits, now)?;
+        Ok(self.pending.remove(&key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(user_per_minute: u32, guild_per_minute: u32, action: RateLimitAction) -> Limits {
+        Limits {
+            user_per_minute,
+            guild_per_minute,
+            action,
+            max_held_chars: 100,
+        }
+    }
+
+    fn is_allowed(admission: &Admission) -> bool {
+        matches!(admission, Admission::Allowed(_))
+    }
+
+    #[test]
+    fn bursts_are_allowed_then_throttled() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(3, 0, RateLimitAction::Drop);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, now)));
+        }
+        assert!(!is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, now)));
+        // Other users have their own bucket
+        assert!(is_allowed(&limiter.admit(1, 11, "hi".to_string(), &limits, now)));
+    }
+
+    #[test]
+    fn guild_limit_applies_to_everyone() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(0, 2, RateLimitAction::Drop);
+        let now = Instant::now();
+        assert!(is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, now)));
+        assert!(is_allowed(&limiter.admit(1, 11, "hi".to_string(), &limits, now)));
+        assert!(!is_allowed(&limiter.admit(1, 12, "hi".to_string(), &limits, now)));
+        assert!(is_allowed(&limiter.admit(2, 12, "hi".to_string(), &limits, now)));
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(6, 0, RateLimitAction::Drop);
+        let start = Instant::now();
+        for _ in 0..6 {
+            limiter.admit(1, 10, "hi".to_string(), &limits, start);
+        }
+        assert!(!is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, start + Duration::from_secs(5))));
+        // 6 per minute is one every 10 seconds
+        assert!(is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, start + Duration::from_secs(10))));
+        assert!(!is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, start + Duration::from_secs(11))));
+    }
+
+    #[test]
+    fn zero_means_unlimited() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(0, 0, RateLimitAction::Drop);
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert!(is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, now)));
+        }
+    }
+
+    #[test]
+    fn only_the_first_drop_notifies() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(1, 0, RateLimitAction::Drop);
+        let start = Instant::now();
+        limiter.admit(1, 10, "hi".to_string(), &limits, start);
+        assert!(matches!(limiter.admit(1, 10, "hi".to_string(), &limits, start), Admission::Dropped { notify: true }));
+        assert!(matches!(limiter.admit(1, 10, "hi".to_string(), &limits, start), Admission::Dropped { notify: false }));
+        // Getting through again resets it
+        let later = start + Duration::from_secs(60);
+        assert!(is_allowed(&limiter.admit(1, 10, "hi".to_string(), &limits, later)));
+        assert!(matches!(limiter.admit(1, 10, "hi".to_string(), &limits, later), Admission::Dropped { notify: true }));
+    }
+
+    #[test]
+    fn coalesced_messages_are_read_in_order() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(1, 0, RateLimitAction::Coalesce);
+        let start = Instant::now();
+        assert!(is_allowed(&limiter.admit(1, 10, "one".to_string(), &limits, start)));
+        assert!(matches!(limiter.admit(1, 10, "two".to_string(), &limits, start), Admission::Held { first: true }));
+        assert!(matches!(limiter.admit(1, 10, "three".to_string(), &limits, start), Admission::Held { first: false }));
+
+        assert_eq!(limiter.flush(1, 10, &limits, start), Err(Duration::from_secs(60)));
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.flush(1, 10, &limits, later), Ok(Some("two three".to_string())));
+        assert_eq!(limiter.flush(1, 10, &limits, later), Ok(None));
+    }
+
+    #[test]
+    fn held_text_is_capped() {
+        let mut limiter = RateLimiter::default();
+        let limits = Limits { max_held_chars: 10, ..limits(1, 0, RateLimitAction::Coalesce) };
+        let start = Instant::now();
+        limiter.admit(1, 10, "one".to_string(), &limits, start);
+        limiter.admit(1, 10, "two".to_string(), &limits, start);
+        assert!(matches!(limiter.admit(1, 10, "three".to_string(), &limits, start), Admission::Held { first: false }));
+        assert!(matches!(limiter.admit(1, 10, "four".to_string(), &limits, start), Admission::Dropped { notify: false }));
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.flush(1, 10, &limits, later), Ok(Some("two three".to_string())));
+    }
+
+    #[test]
+    fn full_buckets_are_forgotten() {
+        let mut limiter = RateLimiter::default();
+        let limits = limits(1, 5, RateLimitAction::Drop);
+        let start = Instant::now();
+        limiter.admit(1, 10, "hi".to_string(), &limits, start);
+        limiter.admit(1, 10, "hi".to_string(), &limits, start);
+        assert_eq!((limiter.users.len(), limiter.guilds.len(), limiter.notified.len()), (1, 1, 1));
+
+        limiter.admit(2, 20, "hi".to_string(), &limits, start + BUCKET_REFILL);
+        assert_eq!(limiter.users.keys().collect::<Vec<_>>(), vec![&(2, 20)]);
+        assert_eq!(limiter.guilds.keys().collect::<Vec<_>>(), vec![&2]);
+        assert!(limiter.notified.is_empty());
+    }
+}
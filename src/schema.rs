@@ -0,0 +1,72 @@
+table! {
+    channel_links (guild_id, channel_id) {
+        guild_id -> Int8,
+        channel_id -> Int8,
+        voice_channel_id -> Nullable<Int8>,
+    }
+}
+
+table! {
+    command_permissions (guild_id, command) {
+        guild_id -> Int8,
+        command -> Text,
+        level -> Text,
+    }
+}
+
+table! {
+    guild_configs (guild_id) {
+        guild_id -> Int8,
+        tts_provider -> Nullable<Text>,
+        announce_speaker -> Bool,
+        announce_window_secs -> Int4,
+        max_message_length -> Int4,
+        overflow_policy -> Text,
+        user_rate_limit -> Int4,
+        guild_rate_limit -> Int4,
+        rate_limit_action -> Text,
+        rate_limit_notify -> Bool,
+        auto_join -> Bool,
+        idle_timeout_mins -> Int4,
+        voice_present_only -> Bool,
+        read_voice_chat -> Bool,
+        default_voice_provider -> Nullable<Text>,
+        default_voice_name -> Nullable<Text>,
+        default_voice_language -> Nullable<Text>,
+        default_voice_gender -> Nullable<Text>,
+        read_everyone -> Bool,
+        dj_role_id -> Nullable<Int8>,
+        prefix -> Nullable<Text>,
+        canned_replies -> Bool,
+    }
+}
+
+table! {
+    pronunciations (guild_id, word) {
+        guild_id -> Int8,
+        word -> Text,
+        replacement -> Text,
+    }
+}
+
+table! {
+    user_preferences (user_id) {
+        user_id -> Int8,
+        language_code -> Text,
+        voice_name -> Text,
+        ssml_gender -> Text,
+        provider -> Text,
+        ssml -> Bool,
+        speaking_rate -> Float8,
+        pitch -> Float8,
+        volume_gain_db -> Float8,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    channel_links,
+    command_permissions,
+    guild_configs,
+    pronunciations,
+    user_preferences,
+);
@@ -0,0 +1,322 @@
+use super::provider::{SpeechRequest, TtsProvider, TtsResult};
+use serenity::prelude::TypeMapKey;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex,
+    },
+};
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, key: &str) -> bool {
+        self.clock += 1;
+        let clock = self.clock;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = clock;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.clock += 1;
+        if let Some(previous) = self.entries.insert(key, CacheEntry { size, last_used: self.clock }) {
+            self.total_bytes -= previous.size;
+        }
+        self.total_bytes += size;
+    }
+
+    /// Removes the least recently used entry, returning its key.
+    fn pop_oldest(&mut self) -> Option<String> {
+        let key = self.entries.iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.to_string())?;
+        if let Some(entry) = self.entries.remove(&key) {
+            self.total_bytes -= entry.size;
+        }
+        Some(key)
+    }
+
+    /// Drops least recently used entries until the total fits in `max_bytes`,
+    /// returning the keys whose files should go.
+    fn shrink_to(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > max_bytes {
+            match self.pop_oldest() {
+                Some(key) => evicted.push(key),
+                None => break,
+            }
+        }
+        evicted
+    }
+}
+
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+/// Synthesized audio kept on disk so repeated phrases don't cost another
+/// API call. Least recently used clips are evicted once the cache grows
+/// past `max_bytes`.
+pub struct AudioCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl TypeMapKey for AudioCache {
+    type Value = Arc<AudioCache>;
+}
+
+impl AudioCache {
+    /// Opens the cache directory, picking up clips left by a previous run.
+    pub fn open(dir: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let mut existing = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let key = entry.file_name().to_string_lossy().to_string();
+            existing.push((metadata.modified()?, key, metadata.len()));
+        }
+        // Oldest first, so the last modified clips count as most recently used.
+        existing.sort();
+
+        let mut index = CacheIndex::default();
+        for (_, key, size) in existing {
+            index.insert(key, size);
+        }
+
+        // This runs once at startup, before any audio is requested
+        for key in index.shrink_to(max_bytes) {
+            if let Err(why) = fs::remove_file(dir.join(&key)) {
+                println!("Err evicting {} from the audio cache: {:?}", key, why);
+            }
+        }
+
+        Ok(AudioCache {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Everything that influences the synthesized audio ends up in the key.
+    pub fn key(provider: &dyn TtsProvider, request: &SpeechRequest) -> String {
+        let capabilities = provider.capabilities();
+        let audio = format!(
+            "{}:{}:{}",
+            request.audio.speaking_rate,
+            request.audio.pitch,
+            request.audio.volume_gain_db,
+        );
+        let mut hasher = Sha256::new();
+        for part in &[
+            provider.id(),
+            &request.voice.name,
+            &request.voice.language_code,
+            capabilities.audio_extension,
+            &audio,
+            if request.ssml { "ssml" } else { "text" },
+            &request.text,
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        format!("{:x}.{}", hasher.finalize(), capabilities.audio_extension)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let known = self.index.lock().expect("Cache index poisoned").touch(key);
+        let audio = if known {
+            tokio::fs::read(self.dir.join(key)).await.ok()
+        } else {
+            None
+        };
+        match audio {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        audio
+    }
+
+    pub async fn put(&self, key: &str, audio: &[u8]) {
+        if audio.len() as u64 > self.max_bytes {
+            return;
+        }
+        if let Err(why) = tokio::fs::write(self.dir.join(key), audio).await {
+            println!("Err writing to the audio cache: {:?}", why);
+            return;
+        }
+        self.index.lock().expect("Cache index poisoned").insert(key.to_string(), audio.len() as u64);
+        self.evict().await;
+    }
+
+    /// Returns the cached clip for this request, synthesizing (and caching)
+    /// it on a miss.
+    pub async fn synthesize(&self, provider: &dyn TtsProvider, request: &SpeechRequest) -> TtsResult<Vec<u8>> {
+        let key = Self::key(provider, request);
+        if let Some(audio) = self.get(&key).await {
+            return Ok(audio);
+        }
+        let audio = provider.synthesize(request).await?;
+        self.put(&key, &audio).await;
+        Ok(audio)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let index = self.index.lock().expect("Cache index poisoned");
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: index.entries.len(),
+            bytes: index.total_bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    async fn evict(&self) {
+        // The index lock can't be held across the file removals
+        let evicted = self.index.lock().expect("Cache index poisoned").shrink_to(self.max_bytes);
+        for key in evicted {
+            if let Err(why) = tokio::fs::remove_file(self.dir.join(&key)).await {
+                println!("Err evicting {} from the audio cache: {:?}", key, why);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::{
+        models::{Voice, VoiceDescriptor},
+        provider::{AudioSettings, Capabilities},
+    };
+    use serenity::async_trait;
+
+    struct FakeProvider;
+
+    #[async_trait]
+    impl TtsProvider for FakeProvider {
+        fn id(&self) -> &'static str {
+            "fake"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                audio_extension: "wav",
+                ssml: true,
+            }
+        }
+
+        async fn list_voices(&self) -> TtsResult<Vec<VoiceDescriptor>> {
+            Ok(Vec::new())
+        }
+
+        async fn synthesize(&self, _request: &SpeechRequest) -> TtsResult<Vec<u8>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn request(text: &str) -> SpeechRequest {
+        SpeechRequest {
+            text: text.to_string(),
+            voice: Voice {
+                language_code: "en-GB".to_string(),
+                name: "en-GB-Wavenet-A".to_string(),
+                ssml_gender: "FEMALE".to_string(),
+                provider: "fake".to_string(),
+            },
+            ssml: false,
+            audio: AudioSettings::default(),
+        }
+    }
+
+    #[test]
+    fn least_recently_used_goes_first() {
+        let mut index = CacheIndex::default();
+        index.insert("a".to_string(), 10);
+        index.insert("b".to_string(), 10);
+        index.insert("c".to_string(), 10);
+        assert!(index.touch("a"));
+        assert!(!index.touch("missing"));
+        assert_eq!(index.pop_oldest(), Some("b".to_string()));
+        assert_eq!(index.pop_oldest(), Some("c".to_string()));
+        assert_eq!(index.pop_oldest(), Some("a".to_string()));
+        assert_eq!(index.pop_oldest(), None);
+        assert_eq!(index.total_bytes, 0);
+    }
+
+    #[test]
+    fn shrinking_stops_once_under_the_cap() {
+        let mut index = CacheIndex::default();
+        index.insert("a".to_string(), 40);
+        index.insert("b".to_string(), 40);
+        index.insert("c".to_string(), 40);
+        assert!(index.shrink_to(120).is_empty());
+        assert_eq!(index.shrink_to(100), vec!["a".to_string()]);
+        assert_eq!(index.total_bytes, 80);
+        assert_eq!(index.shrink_to(0), vec!["b".to_string(), "c".to_string()]);
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn replacing_an_entry_counts_its_size_once() {
+        let mut index = CacheIndex::default();
+        index.insert("a".to_string(), 40);
+        index.insert("a".to_string(), 25);
+        assert_eq!(index.total_bytes, 25);
+        assert_eq!(index.entries.len(), 1);
+    }
+
+    #[test]
+    fn keys_are_stable_and_cover_the_request() {
+        let key = AudioCache::key(&FakeProvider, &request("hello"));
+        // Clips outlive restarts, so the key can't change between builds either
+        assert_eq!(key, "6f5ffbc94e660c0c61ea1e2571d7a691f37082c8b57000e93bd758c5458f3796.wav");
+
+        assert_ne!(key, AudioCache::key(&FakeProvider, &request("hello there")));
+        let mut faster = request("hello");
+        faster.audio.speaking_rate = 1.5;
+        assert_ne!(key, AudioCache::key(&FakeProvider, &faster));
+        let mut ssml = request("hello");
+        ssml.ssml = true;
+        assert_ne!(key, AudioCache::key(&FakeProvider, &ssml));
+        let mut other_voice = request("hello");
+        other_voice.voice.name = "en-GB-Wavenet-B".to_string();
+        assert_ne!(key, AudioCache::key(&FakeProvider, &other_voice));
+    }
+}
@@ -0,0 +1,112 @@
+use super::models::VoiceDescriptor;
+use super::provider::{TtsProviders, TtsResult};
+use serenity::prelude::TypeMapKey;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, time::delay_for};
+
+pub enum VoiceLookup {
+    Found(VoiceDescriptor),
+    /// Not an exact match, but this voice name comes close.
+    DidYouMean(String),
+    Unknown,
+}
+
+/// Every provider's voice list, fetched once at startup and refreshed on a
+/// schedule so commands don't hit the APIs on every invocation.
+pub struct VoiceCatalog {
+    providers: Arc<TtsProviders>,
+    voices: RwLock<HashMap<&'static str, Vec<VoiceDescriptor>>>,
+}
+
+impl TypeMapKey for VoiceCatalog {
+    type Value = Arc<VoiceCatalog>;
+}
+
+impl VoiceCatalog {
+    pub fn new(providers: Arc<TtsProviders>) -> Self {
+        VoiceCatalog {
+            providers,
+            voices: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Refetches every provider's voices. A provider that fails keeps the
+    /// list it had before.
+    pub async fn refresh(&self) {
+        for id in self.providers.ids() {
+            if let Err(why) = self.refresh_provider(id).await {
+                println!("Err refreshing {} voices: {}", id, why);
+            }
+        }
+    }
+
+    async fn refresh_provider(&self, id: &'static str) -> TtsResult<Vec<VoiceDescriptor>> {
+        let provider = match self.providers.get(id) {
+            Some(provider) => provider,
+            None => return Ok(Vec::new()),
+        };
+        let mut voices = provider.list_voices().await?;
+        voices.sort_by(|a, b| a.name.cmp(&b.name));
+        self.voices.write().await.insert(id, voices.clone());
+        Ok(voices)
+    }
+
+    pub fn spawn_refresh(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                delay_for(interval).await;
+                self.refresh().await;
+            }
+        });
+    }
+
+    /// The provider's voices, sorted by name. Fetched on the spot if the
+    /// catalog has nothing for it yet, e.g. because startup failed.
+    pub async fn voices(&self, provider_id: &str) -> TtsResult<Vec<VoiceDescriptor>> {
+        if let Some(voices) = self.voices.read().await.get(provider_id) {
+            if !voices.is_empty() {
+                return Ok(voices.clone());
+            }
+        }
+        match self.providers.ids().into_iter().find(|id| *id == provider_id) {
+            Some(id) => self.refresh_provider(id).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Finds a voice by name, ignoring case, or the closest name to suggest.
+    pub async fn lookup(&self, provider_id: &str, name: &str) -> TtsResult<VoiceLookup> {
+        let voices = self.voices(provider_id).await?;
+        let name = name.trim().to_lowercase();
+        if let Some(voice) = voices.iter().find(|voice| voice.name.to_lowercase() == name) {
+            return Ok(VoiceLookup::Found(voice.clone()));
+        }
+
+        let closest = voices.iter()
+            .map(|voice| (edit_distance(&voice.name.to_lowercase(), &name), voice))
+            .min_by_key(|(distance, _)| *distance);
+        match closest {
+            Some((distance, voice)) if distance <= (name.len() / 3).max(2) => {
+                Ok(VoiceLookup::DidYouMean(voice.name.to_string()))
+            },
+            _ => Ok(VoiceLookup::Unknown),
+        }
+    }
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = substitution
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,37 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, prelude::*},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// A synthesized utterance written to its own uniquely named file, so that
+/// concurrent messages never overwrite each other's audio. The file is
+/// removed again when the clip is dropped.
+pub struct SpeechClip {
+    path: PathBuf,
+}
+
+impl SpeechClip {
+    pub fn write(audio: &[u8], extension: &str) -> io::Result<Self> {
+        let path = env::temp_dir().join(format!("gabby-{}.{}", Uuid::new_v4(), extension));
+        let mut file = File::create(&path)?;
+        // From here on a failed write still removes the file through `Drop`
+        let clip = SpeechClip { path };
+        file.write_all(audio)?;
+        Ok(clip)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpeechClip {
+    fn drop(&mut self) {
+        if let Err(why) = fs::remove_file(&self.path) {
+            println!("Err removing clip {:?}: {:?}", self.path, why);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::{error::Error, fmt, io};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiErrorKind {
+    Quota,
+    InvalidVoice,
+    TextTooLong,
+    Other,
+}
+
+#[derive(Debug)]
+pub enum TtsError {
+    /// The provider isn't set up, e.g. a missing API key.
+    Config(String),
+    /// The request never got a response.
+    Http(reqwest::Error),
+    /// An error status whose body wasn't an API error payload.
+    Status(reqwest::StatusCode, String),
+    /// An error reported by the API itself.
+    Api { kind: ApiErrorKind, status: String, message: String },
+    /// The audio in an otherwise successful response was unusable.
+    Decode(String),
+    /// A local engine failed or produced nothing.
+    Engine(String),
+    Io(io::Error),
+}
+
+impl TtsError {
+    /// Sorts a Google error payload into the cases users can do something about.
+    pub fn from_api(code: u16, status: String, message: String) -> Self {
+        let lowercase = message.to_lowercase();
+        let kind = if code == 429 || status == "RESOURCE_EXHAUSTED" {
+            ApiErrorKind::Quota
+        } else if lowercase.contains("longer than") || lowercase.contains("too long") {
+            ApiErrorKind::TextTooLong
+        } else if lowercase.contains("voice") {
+            ApiErrorKind::InvalidVoice
+        } else {
+            ApiErrorKind::Other
+        };
+        TtsError::Api { kind, status, message }
+    }
+
+    /// What to tell the people in chat when this happens, `prefix` being the
+    /// guild's command prefix.
+    pub fn user_message(&self, prefix: &str) -> String {
+        match self {
+            TtsError::Config(_) => "TTS isn't set up properly, poke whoever runs me".to_string(),
+            TtsError::Http(_) => "I couldn't reach the TTS service, try again in a bit".to_string(),
+            TtsError::Status(status, _) => format!("The TTS service had a hiccup ({})", status),
+            TtsError::Api { kind: ApiErrorKind::Quota, .. } => "We're out of TTS quota for now, try again later".to_string(),
+            TtsError::Api { kind: ApiErrorKind::InvalidVoice, .. } => format!("That voice doesn't work anymore, pick another one with {}register", prefix),
+            TtsError::Api { kind: ApiErrorKind::TextTooLong, .. } => "That message is too long for me to read".to_string(),
+            TtsError::Api { message, .. } => format!("The TTS service refused that: {}", message),
+            TtsError::Decode(_) => "The TTS service sent back garbled audio".to_string(),
+            TtsError::Engine(_) | TtsError::Io(_) => "The local TTS engine failed".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TtsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TtsError::Config(reason) => write!(f, "TTS configuration error: {}", reason),
+            TtsError::Http(why) => write!(f, "TTS request failed: {}", why),
+            TtsError::Status(status, body) => write!(f, "TTS request returned {}: {}", status, body),
+            TtsError::Api { kind, status, message } => write!(f, "TTS API error ({:?}, {}): {}", kind, status, message),
+            TtsError::Decode(reason) => write!(f, "Unable to decode TTS audio: {}", reason),
+            TtsError::Engine(reason) => write!(f, "TTS engine error: {}", reason),
+            TtsError::Io(why) => write!(f, "TTS io error: {}", why),
+        }
+    }
+}
+
+impl Error for TtsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TtsError::Http(why) => Some(why),
+            TtsError::Io(why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for TtsError {
+    fn from(why: reqwest::Error) -> Self {
+        TtsError::Http(why)
+    }
+}
+
+impl From<io::Error> for TtsError {
+    fn from(why: io::Error) -> Self {
+        TtsError::Io(why)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(code: u16, status: &str, message: &str) -> ApiErrorKind {
+        match TtsError::from_api(code, status.to_string(), message.to_string()) {
+            TtsError::Api { kind, .. } => kind,
+            other => panic!("Expected an API error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn quota_errors_go_by_code_or_status() {
+        assert_eq!(kind(429, "RESOURCE_EXHAUSTED", "Quota exceeded for quota metric"), ApiErrorKind::Quota);
+        assert_eq!(kind(403, "RESOURCE_EXHAUSTED", "Too many requests for this voice"), ApiErrorKind::Quota);
+    }
+
+    #[test]
+    fn long_input_wins_over_the_voice() {
+        assert_eq!(kind(400, "INVALID_ARGUMENT", "Either input.text or input.ssml is longer than the limit of 5000 bytes"), ApiErrorKind::TextTooLong);
+        assert_eq!(kind(400, "INVALID_ARGUMENT", "Sentence is too long for this voice"), ApiErrorKind::TextTooLong);
+    }
+
+    #[test]
+    fn unknown_voices_are_recognized() {
+        assert_eq!(kind(400, "INVALID_ARGUMENT", "Voice 'en-US-Nope' does not exist"), ApiErrorKind::InvalidVoice);
+    }
+
+    #[test]
+    fn anything_else_is_other() {
+        assert_eq!(kind(400, "INVALID_ARGUMENT", "Invalid SSML"), ApiErrorKind::Other);
+        assert_eq!(kind(500, "INTERNAL", "Internal error encountered."), ApiErrorKind::Other);
+    }
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::process::{self, Stdio};
+use serenity::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use super::error::TtsError;
+use super::models::*;
+use super::provider::*;
+
+/// Offline speech through a local `espeak-ng` binary, for hosts without
+/// internet access or an API budget.
+pub struct EspeakTts {
+    binary: String,
+}
+
+impl EspeakTts {
+    pub const ID: &'static str = "espeak";
+
+    /// Uses `ESPEAK_PATH` or `espeak-ng` from the `PATH`, returning `None`
+    /// when that binary can't be run.
+    pub fn detect() -> Option<Self> {
+        let binary = env::var("ESPEAK_PATH").unwrap_or_else(|_| "espeak-ng".to_string());
+        let runs = process::Command::new(&binary)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        if runs {
+            Some(EspeakTts { binary })
+        } else {
+            None
+        }
+    }
+}
+
+/// Turns espeak's `--/M` style age/gender column into the SSML gender names
+/// the Google voices use.
+fn ssml_gender(age_gender: &str) -> String {
+    match age_gender.rsplit('/').next() {
+        Some("M") => "MALE",
+        Some("F") => "FEMALE",
+        _ => "NEUTRAL",
+    }.to_string()
+}
+
+// espeak's defaults are 175 words per minute, pitch 50 of 0-99 and
+// amplitude 100 of 0-200; the settings are relative to those.
+fn words_per_minute(speaking_rate: f64) -> u32 {
+    (175.0 * speaking_rate).round().clamp(80.0, 500.0) as u32
+}
+
+fn pitch(semitones: f64) -> u32 {
+    (50.0 + semitones * 2.5).round().clamp(0.0, 99.0) as u32
+}
+
+fn amplitude(volume_gain_db: f64) -> u32 {
+    (100.0 * 10f64.powf(volume_gain_db / 20.0)).round().clamp(0.0, 200.0) as u32
+}
+
+#[async_trait]
+impl TtsProvider for EspeakTts {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            audio_extension: "wav",
+            ssml: true,
+        }
+    }
+
+    async fn list_voices(&self) -> TtsResult<Vec<VoiceDescriptor>> {
+        let output = Command::new(&self.binary)
+            .arg("--voices")
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(TtsError::Engine(format!("{} --voices exited with {}", self.binary, output.status)));
+        }
+
+        // Columns: Pty Language Age/Gender VoiceName File Other-languages
+        let listing = String::from_utf8_lossy(&output.stdout);
+        let voices = listing.lines()
+            .skip(1)
+            .filter_map(|line| {
+                let mut columns = line.split_whitespace();
+                let _priority = columns.next()?;
+                let language = columns.next()?;
+                let age_gender = columns.next()?;
+                Some(VoiceDescriptor {
+                    provider: Self::ID.to_string(),
+                    name: language.to_string(),
+                    language_codes: vec![language.to_string()],
+                    ssml_gender: ssml_gender(age_gender),
+                })
+            })
+            .collect();
+        Ok(voices)
+    }
+
+    async fn synthesize(&self, request: &SpeechRequest) -> TtsResult<Vec<u8>> {
+        // The text goes in through stdin so it's never parsed as an option.
+        let mut command = Command::new(&self.binary);
+        if request.ssml {
+            command.arg("-m");
+        }
+        let mut child = command
+            .arg("-v")
+            .arg(&request.voice.name)
+            .arg("-s")
+            .arg(words_per_minute(request.audio.speaking_rate).to_string())
+            .arg("-p")
+            .arg(pitch(request.audio.pitch).to_string())
+            .arg("-a")
+            .arg(amplitude(request.audio.volume_gain_db).to_string())
+            .arg("--stdin")
+            .arg("--stdout")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(request.text.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(TtsError::Engine(format!("{} exited with {}", self.binary, output.status)));
+        }
+        if output.stdout.is_empty() {
+            return Err(TtsError::Engine(format!("{} produced no audio", self.binary)));
+        }
+        Ok(output.stdout)
+    }
+}
@@ -2,38 +2,115 @@ use std::env;
 use base64_stream::FromBase64Reader;
 use std::io::Cursor;
 use std::io::Read;
+use serenity::async_trait;
+use serde::de::DeserializeOwned;
+use super::error::TtsError;
 use super::models::*;
+use super::provider::*;
 
-pub async fn list_voices() -> Result<Vec<VoiceListEntity>, reqwest::Error> {
-    let key = env::var("GOOGLE_API_KEY").expect("Expected a google api key in this environment");
-    let url = format!("https://texttospeech.googleapis.com/v1beta1/voices?key={}", key);
-    let res = reqwest::get(&url)
-        .await?
-        .json::<VoiceListResponseEntity>().await?;
-    Ok(res.voices)
+pub struct GoogleTts {
+    api_key: String,
+    client: reqwest::Client,
 }
 
-pub async fn message_to_speech(msg: &String, voice: Voice) -> Result<Vec<u8>, reqwest::Error> {
-    let body = VoiceRequest {
-        input: VoiceInput {
-            text: msg.to_string(),
-        },
-        voice,
-        audio_config: AudioConfig {
-            audio_encoding: "OGG_OPUS".to_string(),
-            sample_rate_hertz: 48000,
+impl GoogleTts {
+    pub const ID: &'static str = "google";
+
+    /// Returns `None` when no `GOOGLE_API_KEY` is configured.
+    pub fn from_env() -> Option<Self> {
+        let api_key = env::var("GOOGLE_API_KEY").ok()?;
+        Some(GoogleTts {
+            api_key,
+            client: reqwest::Client::new(),
+        })
+    }
+}
+
+/// Passes successful responses through, turning anything else into the
+/// most specific `TtsError` the body allows.
+async fn check_response(response: reqwest::Response) -> TtsResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await?;
+    match serde_json::from_str::<ApiErrorResponseEntity>(&body) {
+        Ok(payload) => Err(TtsError::from_api(payload.error.code, payload.error.status, payload.error.message)),
+        Err(_) => Err(TtsError::Status(status, body)),
+    }
+}
+
+/// Reads a successful response's JSON body. The service was reached, so a
+/// body that doesn't parse is a `Decode` error rather than an `Http` one.
+async fn decode_body<T: DeserializeOwned>(response: reqwest::Response) -> TtsResult<T> {
+    response.json::<T>().await
+        .map_err(|why| TtsError::Decode(why.to_string()))
+}
+
+#[async_trait]
+impl TtsProvider for GoogleTts {
+    fn id(&self) -> &'static str {
+        Self::ID
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            audio_extension: "ogg",
+            ssml: true,
+        }
+    }
+
+    async fn list_voices(&self) -> TtsResult<Vec<VoiceDescriptor>> {
+        let url = format!("https://texttospeech.googleapis.com/v1beta1/voices?key={}", self.api_key);
+        let response = self.client.get(&url)
+            .send()
+            .await?;
+        let res: VoiceListResponseEntity = decode_body(check_response(response).await?).await?;
+        Ok(res.voices.into_iter()
+            .map(|voice| VoiceDescriptor {
+                provider: Self::ID.to_string(),
+                name: voice.name,
+                language_codes: voice.language_codes,
+                ssml_gender: voice.ssml_gender,
+            })
+            .collect())
+    }
+
+    async fn synthesize(&self, request: &SpeechRequest) -> TtsResult<Vec<u8>> {
+        let body = VoiceRequest {
+            input: if request.ssml {
+                VoiceInput {
+                    text: None,
+                    ssml: Some(request.text.to_string()),
+                }
+            } else {
+                VoiceInput {
+                    text: Some(request.text.to_string()),
+                    ssml: None,
+                }
+            },
+            voice: request.voice.clone(),
+            audio_config: AudioConfig {
+                audio_encoding: "OGG_OPUS".to_string(),
+                sample_rate_hertz: 48000,
+                speaking_rate: request.audio.speaking_rate,
+                pitch: request.audio.pitch,
+                volume_gain_db: request.audio.volume_gain_db,
+            }
+        };
+        let url = format!("https://texttospeech.googleapis.com/v1/text:synthesize?key={}", self.api_key);
+        let response = self.client.post(&url)
+            .json(&body)
+            .send()
+            .await?;
+        let res: VoiceResponse = decode_body(check_response(response).await?).await?;
+        let mut reader = FromBase64Reader::new(Cursor::new(res.audio_content));
+        let mut buff = Vec::new();
+        reader.read_to_end(&mut buff)
+            .map_err(|why| TtsError::Decode(why.to_string()))?;
+        if buff.is_empty() {
+            return Err(TtsError::Decode("empty audio content".to_string()));
         }
-    };
-    let client = reqwest::Client::new();
-    let key = env::var("GOOGLE_API_KEY").expect("Expected a google api key in this environment");
-    let url = format!("https://texttospeech.googleapis.com/v1/text:synthesize?key={}", key);
-    let res = client.post(&url)
-        .json(&body)
-        .send()
-        .await?
-        .json::<VoiceResponse>().await?;
-    let mut reader = FromBase64Reader::new(Cursor::new(res.audio_content));
-    let mut buff = Vec::new();
-    reader.read_to_end(&mut buff).unwrap();
-    Ok(buff)
+        Ok(buff)
+    }
 }
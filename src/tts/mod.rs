@@ -1,2 +1,9 @@
+pub mod cache;
+pub mod catalog;
+pub mod clip;
+pub mod error;
+pub mod espeak;
 pub mod google_tts;
 pub mod models;
+pub mod provider;
+pub mod ssml;
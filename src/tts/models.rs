@@ -6,6 +6,7 @@ pub struct VoiceListEntity {
     pub language_codes: Vec<String>,
     pub name: String,
     pub ssml_gender: String,
+    #[allow(dead_code)]
     pub natural_sample_rate_hertz: u64,
 }
 
@@ -20,18 +21,68 @@ pub struct VoiceResponse {
     pub audio_content: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ApiErrorEntity {
+    pub code: u16,
+    pub message: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiErrorResponseEntity {
+    pub error: ApiErrorEntity,
+}
+
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct VoiceInput {
-    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssml: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Voice {
     pub language_code: String,
     pub name: String,
     pub ssml_gender: String,
+    /// Which `TtsProvider` this voice belongs to, never sent to the API.
+    #[serde(skip)]
+    pub provider: String,
+}
+
+/// A voice as offered by any of the TTS providers.
+#[derive(Debug, Clone)]
+pub struct VoiceDescriptor {
+    pub provider: String,
+    pub name: String,
+    pub language_codes: Vec<String>,
+    pub ssml_gender: String,
+}
+
+impl VoiceDescriptor {
+    /// The model family, e.g. `Wavenet` for `en-US-Wavenet-D`. Voices whose
+    /// names don't follow that pattern are `Standard`.
+    pub fn voice_type(&self) -> &str {
+        let parts: Vec<&str> = self.name.split('-').collect();
+        if parts.len() >= 4 {
+            parts[2]
+        } else {
+            "Standard"
+        }
+    }
+
+    pub fn to_voice(&self) -> Voice {
+        Voice {
+            language_code: self.language_codes.first().cloned().unwrap_or_default(),
+            name: self.name.to_string(),
+            ssml_gender: self.ssml_gender.to_string(),
+            provider: self.provider.to_string(),
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -39,6 +90,9 @@ pub struct Voice {
 pub struct AudioConfig {
     pub audio_encoding: String,
     pub sample_rate_hertz: u32,
+    pub speaking_rate: f64,
+    pub pitch: f64,
+    pub volume_gain_db: f64,
 }
 
 #[derive(Serialize, Debug)]
@@ -0,0 +1,126 @@
+use super::error::TtsError;
+use super::models::{Voice, VoiceDescriptor};
+use crate::models::guild_config::GuildConfig;
+use serenity::{async_trait, prelude::TypeMapKey};
+use std::{collections::HashMap, sync::Arc};
+
+pub type TtsResult<T> = Result<T, TtsError>;
+
+/// What a provider can do, so callers can adapt the request and the
+/// playback to it.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    /// File extension of the audio returned by `synthesize`.
+    pub audio_extension: &'static str,
+    /// Whether `SpeechRequest::ssml` input is understood.
+    pub ssml: bool,
+}
+
+/// How a voice is tuned, in the units Google uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioSettings {
+    pub speaking_rate: f64,
+    /// Semitones up or down from the voice's natural pitch.
+    pub pitch: f64,
+    pub volume_gain_db: f64,
+}
+
+impl AudioSettings {
+    pub const SPEAKING_RATE_RANGE: (f64, f64) = (0.25, 4.0);
+    pub const PITCH_RANGE: (f64, f64) = (-20.0, 20.0);
+    pub const VOLUME_GAIN_DB_RANGE: (f64, f64) = (-96.0, 16.0);
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            speaking_rate: 1.0,
+            pitch: 0.0,
+            volume_gain_db: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpeechRequest {
+    pub text: String,
+    pub voice: Voice,
+    /// `text` is SSML markup rather than plain text.
+    pub ssml: bool,
+    pub audio: AudioSettings,
+}
+
+#[async_trait]
+pub trait TtsProvider: Send + Sync {
+    /// Stable identifier, stored alongside every registered voice.
+    fn id(&self) -> &'static str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    async fn list_voices(&self) -> TtsResult<Vec<VoiceDescriptor>>;
+
+    async fn synthesize(&self, request: &SpeechRequest) -> TtsResult<Vec<u8>>;
+}
+
+pub struct TtsProviders {
+    providers: HashMap<&'static str, Arc<dyn TtsProvider>>,
+    default_id: String,
+}
+
+impl TypeMapKey for TtsProviders {
+    type Value = Arc<TtsProviders>;
+}
+
+impl TtsProviders {
+    pub fn new() -> Self {
+        TtsProviders {
+            providers: HashMap::new(),
+            default_id: String::new(),
+        }
+    }
+
+    pub fn register(&mut self, provider: Arc<dyn TtsProvider>) {
+        self.providers.insert(provider.id(), provider);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn TtsProvider>> {
+        self.providers.get(id).cloned()
+    }
+
+    pub fn ids(&self) -> Vec<&'static str> {
+        let mut ids: Vec<&'static str> = self.providers.keys().cloned().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Picks the provider for guilds that didn't choose one, it has to be
+    /// registered already.
+    pub fn set_default(&mut self, id: &str) -> Result<(), String> {
+        if self.get(id).is_none() {
+            return Err(format!("the {} provider isn't configured, pick one of: {}", id, self.ids().join(", ")));
+        }
+        self.default_id = id.to_string();
+        Ok(())
+    }
+
+    pub fn default_provider(&self) -> Arc<dyn TtsProvider> {
+        self.get(&self.default_id).expect("Expected the default TTS provider to be registered")
+    }
+
+    /// The provider a guild picked with `g/provider`, falling back to the default.
+    pub fn for_guild(&self, config: &GuildConfig) -> Arc<dyn TtsProvider> {
+        config.tts_provider.as_ref()
+            .and_then(|id| self.get(id))
+            .unwrap_or_else(|| self.default_provider())
+    }
+
+    /// The provider a registered voice belongs to, if it is still available.
+    pub fn for_voice(&self, voice: &Voice) -> TtsResult<Arc<dyn TtsProvider>> {
+        self.get(&voice.provider)
+            .ok_or_else(|| TtsError::Config(format!("the {} provider isn't configured", voice.provider)))
+    }
+}
@@ -0,0 +1,88 @@
+use regex::Regex;
+
+/// Google rejects inputs longer than this, markup included.
+pub const MAX_SSML_BYTES: usize = 5000;
+
+lazy_static! {
+    static ref EMPHASIS: Regex = Regex::new(r"\*([^*\s][^*]*)\*").unwrap();
+    static ref PAUSE: Regex = Regex::new(r"(\.{3,}|…)").unwrap();
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Converts a chat message into SSML. Only a small, safe subset of markup is
+/// understood: `*word*` is emphasized and `...` becomes a pause. Everything
+/// else is escaped, so users can't inject tags of their own.
+///
+/// Returns `None` when the result would be too long to send.
+pub fn to_ssml(text: &str) -> Option<String> {
+    let escaped = escape(text);
+    let ssml = EMPHASIS.replace_all(&escaped, r#"<emphasis level="strong">$1</emphasis>"#);
+    let ssml = PAUSE.replace_all(&ssml, r#"<break time="500ms"/>"#);
+
+    let ssml = format!("<speak>{}</speak>", ssml);
+    if ssml.len() > MAX_SSML_BYTES {
+        return None;
+    }
+    Some(ssml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_in_user_text_is_escaped() {
+        assert_eq!(
+            to_ssml("</speak><speak>hi").unwrap(),
+            "<speak>&lt;/speak&gt;&lt;speak&gt;hi</speak>",
+        );
+        assert_eq!(
+            to_ssml(r#"wait <break time="99s"/> now"#).unwrap(),
+            "<speak>wait &lt;break time=&quot;99s&quot;/&gt; now</speak>",
+        );
+    }
+
+    #[test]
+    fn ampersands_and_quotes_are_escaped() {
+        assert_eq!(to_ssml("salt & pepper").unwrap(), "<speak>salt &amp; pepper</speak>");
+        assert_eq!(to_ssml(r#"it's "fine""#).unwrap(), "<speak>it&apos;s &quot;fine&quot;</speak>");
+    }
+
+    #[test]
+    fn asterisks_become_emphasis() {
+        assert_eq!(
+            to_ssml("that is *so* good").unwrap(),
+            r#"<speak>that is <emphasis level="strong">so</emphasis> good</speak>"#,
+        );
+        assert_eq!(to_ssml("2 * 3 * 4").unwrap(), "<speak>2 * 3 * 4</speak>");
+    }
+
+    #[test]
+    fn ellipses_become_pauses() {
+        assert_eq!(to_ssml("well... ok").unwrap(), r#"<speak>well<break time="500ms"/> ok</speak>"#);
+        assert_eq!(to_ssml("well… ok").unwrap(), r#"<speak>well<break time="500ms"/> ok</speak>"#);
+    }
+
+    #[test]
+    fn output_over_the_limit_is_rejected() {
+        let limit = MAX_SSML_BYTES - "<speak></speak>".len();
+        assert!(to_ssml(&"a".repeat(limit)).is_some());
+        assert!(to_ssml(&"a".repeat(limit + 1)).is_none());
+        // Escaping counts towards the limit
+        assert!(to_ssml(&"&".repeat(limit / 2)).is_none());
+    }
+}